@@ -1,6 +1,6 @@
-use std::{io::{stdin, stdout, BufRead, Read, Write}, net::TcpStream, os::unix::net::UnixStream, sync::{Arc, Mutex}, thread};
+use std::{collections::HashMap, io::{stdin, stdout, BufRead, Read, Write}, net::TcpStream, os::unix::net::UnixStream, sync::{Arc, Mutex}, thread};
 
-use server_app::{AppError, AppResult, Command, Packet, ReadBytes, WriteBytes, Connection};
+use server_app::{graphemes, AppError, AppResult, Command, Packet, ReadBytes, WriteBytes, Connection, LetterFeedback, MatchOptions, SERVER_ID};
 
 fn main() -> AppResult<()> {
     Client::new().run()
@@ -19,6 +19,16 @@ struct ClientConfig {
     state: GameState,
     id: Option<String>,
     opponent_id: Option<String>,
+    invited: Option<String>,
+    room: Option<String>,
+    /// Ids of the own team in a team lobby or match, including the own one.
+    team: Vec<String>,
+    /// The language chosen with `lang`, used for new matches unless they set their own.
+    language: Option<String>,
+    /// Names of other players by id, the server sends them before commands with their ids.
+    names: HashMap<String, String>,
+    /// Printed once the server confirms the last command with `Ok`, instead of the generic message.
+    confirmation: Option<&'static str>,
 }
 
 impl ClientConfig {
@@ -27,8 +37,27 @@ impl ClientConfig {
             state: Default::default(),
             id: None,
             opponent_id: None,
+            invited: None,
+            room: None,
+            team: Vec::new(),
+            language: None,
+            names: HashMap::new(),
+            confirmation: None,
         }
     }
+
+    /// The name of the player followed by their id, only the id if the name is not known.
+    fn player(&self, id: &str) -> String {
+        match self.names.get(id) {
+            Some(name) => format!("{} ({})", name, id),
+            None => id.to_string(),
+        }
+    }
+
+    /// Whether the winner is this player or one of their teammates.
+    fn is_own_win(&self, winner: &String) -> bool {
+        self.id.as_ref() == Some(winner) || self.team.contains(winner)
+    }
 }
 
 struct Client {
@@ -46,6 +75,22 @@ impl Client {
         }
     }
 
+    /// Parse match options from the arguments of a command, bare words select the ruleset.
+    /// The match is played in the chosen language unless the arguments set another one.
+    fn parse_options<'a>(args: impl Iterator<Item = &'a str>, language: Option<String>) -> Result<MatchOptions, String> {
+        let mut options = MatchOptions::default();
+        if let Some(language) = language {
+            options.language = language;
+        }
+        for arg in args {
+            match arg.contains('=') {
+                true => options.apply(arg)?,
+                false => options.apply(&format!("rules={}", arg))?,
+            }
+        }
+        Ok(options)
+    }
+
     /// Read a line from stdin with a prompt.
     fn prompt(prompt: &str) -> AppResult<String> {
         let mut line = String::new();
@@ -138,9 +183,13 @@ impl Client {
 
             if state == GameState::Guessing {
                 let guess = Self::prompt("Enter guess")?;
+                // only the server gives hints on request, in solo games
+                let solo = config.lock().unwrap().opponent_id == Some(SERVER_ID.to_string());
                 let mut lock = connection.lock().unwrap();
                 let stream = lock.as_mut().expect("No connection").writer();
-                if !guess.is_empty() {
+                if solo && guess == "?" {
+                    Self::send(stream, Command::HintRequest)?;
+                } else if !guess.is_empty() {
                     Self::send(stream, Command::Guess(guess))?;
                 }
                 continue;
@@ -149,17 +198,39 @@ impl Client {
             if state == GameState::Menu || state == GameState::Hinting {
                 let in_menu = state == GameState::Menu;
                 let command = Self::prompt("")?;
+                let language = config.lock().unwrap().language.clone();
 
                 let command = match command.as_str() {
                     "opponents" => Some(Command::OpponentsRequest),
                     "help" => {
-                        let commands = vec![
+                        let commands = [
                             ("opponents", "list opponents"),
                             ("hint [new_hint]", "send a hint"),
                             ("exit", "exit the match"),
                             ("surrender", "surrender the match"),
                             ("help", "show all commands"),
-                            ("match [opponent_id]", "start a match"),
+                            ("match [opponent_id] [ruleset] [option=value...]", "invite a player to a match, rulesets: freeform, reveal, wordle, options: guesses=6 time=120 turn=30 hints=3 length=4-8 lang=lb rounds=3"),
+                            ("accept [opponent_id]", "accept a match invite"),
+                            ("decline [opponent_id]", "decline a match invite"),
+                            ("cancel", "cancel your match invite"),
+                            ("rematch [swap]", "ask the opponent of your last match for a rematch, optionally with swapped roles"),
+                            ("queue", "wait for a random opponent"),
+                            ("leave", "leave the queue"),
+                            ("room [ruleset] [option=value...]", "open a room and set the word for any number of guessers"),
+                            ("rooms", "list open rooms"),
+                            ("name [nickname]", "choose the name other players see instead of your id, without one your id is shown again"),
+                            ("lang [lb|de|fr|en]", "only see and get matched with players of this language, without one any language is fine"),
+                            ("solo [ruleset] [option=value...]", "guess a word picked by the server, type ? while guessing for the next hint"),
+                            ("join [room_id]", "join a room as a guesser"),
+                            ("leaveroom", "leave your room, closing it if you are the hinter"),
+                            ("teams [ruleset] [option=value...]", "open a lobby for a match between two teams"),
+                            ("team [lobby_id] [1|2]", "join or switch to a team of a lobby"),
+                            ("start", "start the match of your lobby"),
+                            ("leaveteams", "leave your lobby"),
+                            ("say [message]", "send a message to your team"),
+                            ("tournaments", "list tournaments"),
+                            ("jointournament [tournament_id]", "register for a tournament, your matches start on their own"),
+                            ("leavetournament [tournament_id]", "withdraw from a tournament before it starts"),
                         ];
 
                         let commands = commands.iter().map(|(cmd, desc)| format!("\n    {cmd} - {desc}")).collect::<String>();
@@ -168,12 +239,22 @@ impl Client {
                     }
                     s if s.starts_with("match ") => {
                         if in_menu {
-                            match s.splitn(2, " ").nth(1) {
+                            let mut args = s.split_whitespace().skip(1);
+
+                            match args.next() {
                                 Some(id) => {
-                                    let mut config = config.lock().unwrap();
-                                    config.opponent_id = Some(id.to_string());
-                                    config.state = GameState::Hinting;
-                                    Some(Command::RequestMatch(id.to_string()))
+                                    match Self::parse_options(args, language) {
+                                        Ok(options) => {
+                                            let mut config = config.lock().unwrap();
+                                            println!("Invite sent to player {} ({})", config.player(id), options);
+                                            config.invited = Some(id.to_string());
+                                            Some(Command::RequestMatch(id.to_string(), options))
+                                        },
+                                        Err(err) => {
+                                            println!("{}", err);
+                                            None
+                                        }
+                                    }
                                 },
                                 None => {
                                     println!("Invalid player id");
@@ -185,12 +266,165 @@ impl Client {
                             None
                         }
                     }
-                    s if s.starts_with("hint ") => {
+                    s if s.starts_with("accept ") || s.starts_with("decline ") => {
+                        if in_menu {
+                            match s.split_once(" ") {
+                                Some(("accept", id)) => Some(Command::AcceptMatch(id.to_string())),
+                                Some((_, id)) => Some(Command::DeclineMatch(id.to_string())),
+                                None => None,
+                            }
+                        } else {
+                            println!("cannot answer invites while playing");
+                            None
+                        }
+                    }
+                    "cancel" => {
+                        match config.lock().unwrap().invited.take() {
+                            Some(id) => {
+                                println!("Invite to player {} cancelled", id);
+                                Some(Command::CancelMatch(id))
+                            },
+                            None => {
+                                println!("you have no pending invite");
+                                None
+                            }
+                        }
+                    },
+                    "rematch" | "rematch swap" => {
+                        if in_menu {
+                            Some(Command::Rematch(command.ends_with("swap")))
+                        } else {
+                            println!("cannot start a match while playing");
+                            None
+                        }
+                    },
+                    "queue" => {
+                        if in_menu {
+                            println!("Waiting for an opponent...");
+                            Some(Command::JoinQueue)
+                        } else {
+                            println!("cannot join the queue while playing");
+                            None
+                        }
+                    },
+                    "leave" => {
+                        config.lock().unwrap().confirmation = Some("Left the queue");
+                        Some(Command::LeaveQueue)
+                    },
+                    s if s == "room" || s.starts_with("room ") => {
+                        match Self::parse_options(s.split_whitespace().skip(1), language) {
+                            Ok(options) if in_menu => {
+                                println!("Opening a {} room...", options);
+                                Some(Command::CreateRoom(options))
+                            },
+                            Ok(_) => {
+                                println!("cannot open a room while playing");
+                                None
+                            },
+                            Err(err) => {
+                                println!("{}", err);
+                                None
+                            }
+                        }
+                    },
+                    "rooms" => Some(Command::RoomsRequest),
+                    s if s == "name" || s.starts_with("name ") => {
+                        let name = s.strip_prefix("name").unwrap_or_default().trim();
+                        Some(Command::SetName(name.to_string()))
+                    },
+                    s if s == "lang" || s.starts_with("lang ") => {
+                        let language = s.split_whitespace().nth(1).unwrap_or_default();
+                        if language.is_empty() || MatchOptions::LANGUAGES.contains(&language) {
+                            config.lock().unwrap().language = (!language.is_empty()).then(|| language.to_string());
+                            Some(Command::SetLanguage(language.to_string()))
+                        } else {
+                            println!("Unknown language, available: {}", MatchOptions::LANGUAGES.join(", "));
+                            None
+                        }
+                    },
+                    s if s == "solo" || s.starts_with("solo ") => {
+                        match Self::parse_options(s.split_whitespace().skip(1), language) {
+                            Ok(options) if in_menu => Some(Command::Solo(options)),
+                            Ok(_) => {
+                                println!("cannot practice while playing");
+                                None
+                            },
+                            Err(err) => {
+                                println!("{}", err);
+                                None
+                            }
+                        }
+                    },
+                    s if s == "teams" || s.starts_with("teams ") => {
+                        match Self::parse_options(s.split_whitespace().skip(1), language) {
+                            Ok(options) if in_menu => Some(Command::CreateTeams(options)),
+                            Ok(_) => {
+                                println!("cannot form teams while playing");
+                                None
+                            },
+                            Err(err) => {
+                                println!("{}", err);
+                                None
+                            }
+                        }
+                    },
+                    s if s.starts_with("team ") => {
+                        let args = s.split_whitespace().skip(1).collect::<Vec<_>>();
+                        match args.as_slice() {
+                            [id, team] => match team.parse() {
+                                Ok(team) => Some(Command::JoinTeam(id.to_string(), team)),
+                                Err(_) => {
+                                    println!("Invalid team, choose 1 or 2");
+                                    None
+                                }
+                            },
+                            _ => {
+                                println!("Usage: team [lobby_id] [1|2]");
+                                None
+                            }
+                        }
+                    },
+                    "start" => Some(Command::StartTeams),
+                    "tournaments" => Some(Command::TournamentsRequest),
+                    s if s.starts_with("jointournament ") => s.split_once(" ").map(|(_, id)| Command::JoinTournament(id.trim().to_string())),
+                    s if s.starts_with("leavetournament ") => s.split_once(" ").map(|(_, id)| Command::LeaveTournament(id.trim().to_string())),
+                    "leaveteams" => {
+                        config.lock().unwrap().team.clear();
+                        Some(Command::LeaveTeams)
+                    },
+                    s if s.starts_with("say ") => s.split_once(" ").map(|(_, message)| Command::TeamChat(message.to_string())),
+                    s if s.starts_with("join ") => {
                         if in_menu {
+                            s.split_once(" ").map(|(_, id)| Command::JoinRoom(id.trim().to_string()))
+                        } else {
+                            println!("cannot join a room while playing");
+                            None
+                        }
+                    },
+                    "leaveroom" => {
+                        let mut config = config.lock().unwrap();
+                        match config.room.take() {
+                            Some(id) => {
+                                println!("Left room {}", id);
+                                config.state = GameState::Menu;
+                                Some(Command::LeaveRoom)
+                            },
+                            None => {
+                                println!("you are not in a room");
+                                None
+                            }
+                        }
+                    },
+                    s if s.starts_with("hint ") => {
+                        let in_team = {
+                            let config = config.lock().unwrap();
+                            config.room.is_some() || !config.team.is_empty()
+                        };
+                        if in_menu && !in_team {
                             println!("you are not in a game");
                             None
                         } else {
-                            match s.splitn(2, " ").nth(1) {
+                            match s.split_once(" ").map(|(_, rest)| rest) {
                                 Some(hint) => Some(Command::Hint(hint.to_string())),
                                 None => {
                                     println!("Invalid hint");
@@ -219,7 +453,7 @@ impl Client {
                         }
                     },
                     s => {
-                        if s.len() > 0 {
+                        if !s.is_empty() {
                             println!("Invalid command, type 'help' to see available commands");
                         }
                         None
@@ -245,12 +479,20 @@ impl Client {
                 println!("Type 'help' to see available commands");
             },
             Command::PasswordRequest => {
-                let password = Self::prompt("Enter password")?;
+                let input = Self::prompt("Enter the server password, 'login [username] [password]' or 'register [username] [password]'")?;
+                let command = match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    ["login", username, password] => Command::Login(username.to_string(), password.to_string()),
+                    ["register", username, password] => Command::Register(username.to_string(), password.to_string()),
+                    _ => Command::Password(input),
+                };
+
                 let mut lock = self.connection.lock().unwrap();
                 let stream = lock.as_mut().expect("No connection").writer();
-                Self::send(stream, Command::Password(password))?;
+                Self::send(stream, command)?;
             },
             Command::Opponents(opponents) => {
+                let config = self.config.lock().unwrap();
+                let opponents = opponents.iter().map(|id| config.player(id)).collect::<Vec<_>>();
                 println!("Opponents: {:?}", opponents);
             },
             Command::Hint(hint) => {
@@ -258,13 +500,16 @@ impl Client {
             },
             Command::Guess(guess) => {
                 let config = self.config.lock().unwrap();
-                println!("Player {} guessed: {}", config.opponent_id.as_ref().expect("No opponent"), guess);
+                println!("Player {} guessed: {}", config.player(config.opponent_id.as_ref().expect("No opponent")), guess);
             },
             Command::PlayerJoined(id) => {
-                println!("Player {} joined", id);
                 let mut config = self.config.lock().unwrap();
+                println!("Player {} joined", config.player(&id));
                 config.opponent_id = Some(id);
             },
+            Command::PlayerName(id, name) => {
+                self.config.lock().unwrap().names.insert(id, name);
+            },
             Command::SetGuess(guess) => {
                 println!("Guess a word: {}\nPress ENTER to start", guess);
                 let mut config = self.config.lock().unwrap();
@@ -283,13 +528,25 @@ impl Client {
             Command::Error(message) => {
                 let mut config = self.config.lock().unwrap();
                 config.opponent_id = None;
-                config.state = GameState::Menu;
+                config.invited = None;
+                config.confirmation = None;
+                // a refused login is followed by another password request
+                if config.state != GameState::Auth {
+                    config.state = GameState::Menu;
+                }
                 eprintln!("Error: {}", message);
             },
             Command::Unknown(message) => {
                 eprintln!("Unknown command: {}", message);
             },
             Command::RequestMatchGuess => {
+                {
+                    let mut config = self.config.lock().unwrap();
+                    if let Some(id) = config.invited.take() {
+                        config.opponent_id = Some(id);
+                    }
+                    config.state = GameState::Hinting;
+                }
                 println!("Starting new match...");
                 let mut guess = Self::prompt("Set a word to guess")?;
                 while guess.is_empty() {
@@ -303,13 +560,134 @@ impl Client {
             Command::PlayerLeft => {
                 let mut config = self.config.lock().unwrap();
                 config.opponent_id = None;
-                if config.state == GameState::Guessing {
-                    println!("Player left, match ended");
-                }else {
-                    println!("Player surrendered, you win!");
+                println!("Player left, you win!");
+                config.state = GameState::Menu;
+            },
+            Command::MatchInvite(id, options) => {
+                let player = self.config.lock().unwrap().player(&id);
+                println!("Player {} invited you to a {} match, type 'accept {}' or 'decline {}'", player, options, id, id);
+            },
+            Command::GameOver(winner, reason) => {
+                let mut config = self.config.lock().unwrap();
+                config.opponent_id = None;
+                if winner.is_empty() {
+                    println!("Game over: {}", reason);
+                } else if config.is_own_win(&winner) {
+                    println!("You win, {}!", reason);
+                } else {
+                    println!("You lose, {}!", reason);
                 }
                 config.state = GameState::Menu;
-            }
+            },
+            Command::Reveal(mask) => {
+                println!("Word: {}", mask);
+            },
+            Command::RemainingGuesses(remaining) => {
+                println!("{} wrong guesses left", remaining);
+            },
+            Command::TimeLeft(seconds) => {
+                println!("{} seconds left", seconds);
+            },
+            Command::MatchScore(own, opponent) => {
+                println!("Match score: {} - {}", own, opponent);
+            },
+            Command::MatchOver(winner) => {
+                let mut config = self.config.lock().unwrap();
+                if winner.is_empty() {
+                    println!("The match is a draw");
+                } else if config.is_own_win(&winner) {
+                    println!("You won the match!");
+                } else {
+                    println!("You lost the match");
+                }
+                // team matches always have several rounds, so this comes after the last game of the team
+                config.team.clear();
+                println!("Type 'rematch' to play again");
+            },
+            Command::Feedback(guess, feedback) => {
+                // [x] is at the right position, (x) is somewhere else in the word
+                let letters = graphemes(&guess).into_iter().zip(feedback).map(|(letter, feedback)| match feedback {
+                    LetterFeedback::Correct => format!("[{}]", letter),
+                    LetterFeedback::Present => format!("({})", letter),
+                    LetterFeedback::Absent => format!(" {} ", letter),
+                }).collect::<String>();
+                println!("Feedback: {}", letters);
+            },
+            Command::RematchRequest(id, swap) => {
+                let roles = if swap { "with swapped roles" } else { "with the same roles" };
+                let player = self.config.lock().unwrap().player(&id);
+                println!("Player {} wants a rematch {}, type 'rematch' or 'accept {}'", player, roles, id);
+            },
+            Command::DeclineMatch(id) => {
+                let mut config = self.config.lock().unwrap();
+                println!("Player {} declined your invite", config.player(&id));
+                config.invited = None;
+            },
+            Command::CancelMatch(id) => {
+                println!("Player {} cancelled their invite", self.config.lock().unwrap().player(&id));
+            },
+            Command::InviteExpired(id) => {
+                let mut config = self.config.lock().unwrap();
+                println!("Invite with player {} expired", config.player(&id));
+                if config.invited.as_ref() == Some(&id) {
+                    config.invited = None;
+                }
+            },
+            Command::Rooms(rooms) => {
+                println!("Rooms: {:?}", rooms);
+            },
+            Command::RoomJoined(id) => {
+                println!("You are in room {}, type 'leaveroom' to leave it", id);
+                self.config.lock().unwrap().room = Some(id);
+            },
+            Command::RoomMembers(guessers) => {
+                let config = self.config.lock().unwrap();
+                let guessers = guessers.iter().map(|id| config.player(id)).collect::<Vec<_>>();
+                println!("Guessers in the room: {:?}", guessers);
+            },
+            Command::PlayerGuess(id, guess) => {
+                println!("Player {} guessed: {}", self.config.lock().unwrap().player(&id), guess);
+            },
+            Command::RoomRanking(ranking) => {
+                if ranking.is_empty() {
+                    println!("Round over, nobody found the word");
+                } else {
+                    let config = self.config.lock().unwrap();
+                    let ranking = ranking.iter().enumerate().map(|(i, id)| format!("\n    {}. player {}", i + 1, config.player(id))).collect::<String>();
+                    println!("Round over, ranking: {}", ranking);
+                }
+            },
+            Command::TeamMembers(id, first, second) => {
+                let mut config = self.config.lock().unwrap();
+                let [named_first, named_second] = [&first, &second].map(|team| team.iter().map(|id| config.player(id)).collect::<Vec<_>>());
+                println!("Lobby {}: team 1 {:?}, team 2 {:?}", id, named_first, named_second);
+                let own = config.id.clone().unwrap_or_default();
+                config.team = [first, second].into_iter().find(|team| team.contains(&own)).unwrap_or_default();
+            },
+            Command::TeamMessage(id, message) => {
+                println!("Player {} (team): {}", self.config.lock().unwrap().player(&id), message);
+            },
+            Command::Tournaments(tournaments) => {
+                let tournaments = tournaments.iter().map(|tournament| format!("\n    {}", tournament)).collect::<String>();
+                println!("Tournaments: {}", tournaments);
+            },
+            Command::Ok => {
+                let confirmation = self.config.lock().unwrap().confirmation.take();
+                println!("{}", confirmation.unwrap_or("Done"));
+            },
+            Command::RoomClosed => {
+                let mut config = self.config.lock().unwrap();
+                config.room = None;
+                config.opponent_id = None;
+                config.state = GameState::Menu;
+                println!("The hinter left, the room is closed");
+            },
+            Command::Surrender => {
+                let mut config = self.config.lock().unwrap();
+                config.opponent_id = None;
+                println!("Player surrendered, you win!");
+                config.state = GameState::Menu;
+            },
 
             _ => println!("Received unhandled command: {:?}", command),
         };
@@ -17,6 +17,10 @@ pub enum AppError {
     TooLarge,
     InvalidConnection,
     Unauthorized,
+    Game(GameError),
+    InvalidArgument(String),
+    /// A password could not be hashed.
+    Password(argon2::password_hash::Error),
 }
 
 impl From<std::io::Error> for AppError {
@@ -24,3 +28,9 @@ impl From<std::io::Error> for AppError {
         AppError::Io(value)
     }
 }
+
+impl From<GameError> for AppError {
+    fn from(value: GameError) -> Self {
+        AppError::Game(value)
+    }
+}
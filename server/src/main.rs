@@ -3,7 +3,7 @@ use std::env::args;
 use server_app::*;
 
 fn main() -> AppResult<()> {
-    let password = args().nth(1).unwrap_or_else(|| String::from("supersecret123"));    
+    let config = ServerConfig::from_args(args().skip(1))?;
 
-    Server::new(password).run()    
+    Server::new(config)?.run()
 }
@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use crate::AppResult;
+use crate::{AppResult, LetterFeedback, MatchOptions};
 
 use super::{ReadBytes, WriteBytes};
 
@@ -13,7 +13,7 @@ pub enum Command {
     SetId(String),
     OpponentsRequest,
     Opponents(Vec<String>),
-    RequestMatch(String),
+    RequestMatch(String, MatchOptions),
     PlayerJoined(String),
     Guess(String),
     Hint(String),
@@ -22,7 +22,93 @@ pub enum Command {
     RequestMatchGuess,
     SetGuess(String),
     PlayerLeft,
+    MatchInvite(String, MatchOptions),
+    AcceptMatch(String),
+    DeclineMatch(String),
+    CancelMatch(String),
+    InviteExpired(String),
+    JoinQueue,
+    /// Leave the matchmaking queue, answered with [`Command::Ok`].
+    LeaveQueue,
+    Reveal(String),
+    Feedback(String, Vec<LetterFeedback>),
+    /// The game ended without the word being guessed, carries the winner id (empty for none) and the reason.
+    GameOver(String, String),
+    /// Wrong guesses the guesser has left, sent after every guess in matches with a limit.
+    RemainingGuesses(u16),
+    /// Seconds until the next deadline of the game, pushed to both players when a turn starts and as a warning.
+    TimeLeft(u16),
+    /// Rounds won by the receiving player and by their opponent, sent after every round of a best-of match.
+    MatchScore(u16, u16),
+    /// A best-of match is over, carries the winner id, empty for a draw.
+    MatchOver(String),
+    /// Ask the opponent of the last match for another one with the same options, `true` swaps the roles.
+    /// Answering with a rematch of their own accepts it.
+    Rematch(bool),
+    /// The opponent of the last match wants a rematch, carries their id and whether the roles are swapped.
+    RematchRequest(String, bool),
+    /// Open a room with the sender as its hinter, the options apply to every game played in it.
+    CreateRoom(MatchOptions),
+    /// Join the room with the given id as a guesser.
+    JoinRoom(String),
+    /// Leave the current room, the room is closed if the hinter leaves.
+    LeaveRoom,
+    RoomsRequest,
+    /// Ids of all open rooms.
+    Rooms(Vec<String>),
+    /// The sender is now a member of the room with the given id.
+    RoomJoined(String),
+    /// Ids of the guessers in the room, sent to every member when someone joins or leaves.
+    RoomMembers(Vec<String>),
+    /// A guess attributed to the player who made it, carries their id and the guess.
+    /// Sent to the hinter of a room and to everyone else in a team game.
+    PlayerGuess(String, String),
+    /// Ids of the guessers who found the word, fastest first, sent to every member once the round is over.
+    RoomRanking(Vec<String>),
+    /// The hinter left and the room is gone.
+    RoomClosed,
+    /// Open a team lobby with the sender in the first team, the options apply to the team match.
+    CreateTeams(MatchOptions),
+    /// Join team 1 or 2 of the lobby with the given id, or switch to it.
+    JoinTeam(String, u8),
+    LeaveTeams,
+    /// Start the match of the lobby, only allowed for the player who opened it.
+    StartTeams,
+    /// Id of the lobby and the members of both teams, sent to every member when the teams change and when the match starts.
+    TeamMembers(String, Vec<String>, Vec<String>),
+    /// A message to the teammates of the sender.
+    TeamChat(String),
+    /// A message from a teammate, carries their id and the message.
+    TeamMessage(String, String),
+    TournamentsRequest,
+    /// A short description of every tournament, starting with its id.
+    Tournaments(Vec<String>),
+    /// Register for the tournament with the given id, answered with [`Command::Ok`].
+    JoinTournament(String),
+    /// Withdraw the registration before the tournament starts, answered with [`Command::Ok`].
+    LeaveTournament(String),
+    /// Start a game against the server, which picks the word from its word list and gives the hints.
+    Solo(MatchOptions),
+    /// Ask the server for the next hint in a solo game, answered with [`Command::Hint`].
+    HintRequest,
+    /// The language the sender wants to play in, only players sharing it are listed as opponents and matched from the queue.
+    /// An empty language accepts any, answered with [`Command::Ok`].
+    SetLanguage(String),
+    /// Create an account with the username and password and log in with it, instead of sending [`Command::Password`].
+    /// Answered with [`Command::SetId`], or with [`Command::Error`] and another [`Command::PasswordRequest`] if the username is taken or invalid.
+    Register(String, String),
+    /// Log in with the username and password of an account, answered like [`Command::Register`].
+    Login(String, String),
+    /// The name the sender wants to be shown with instead of their id, an empty name goes back to the id.
+    /// Answered with [`Command::Ok`], or [`Command::Error`] if the name is invalid or taken.
+    SetName(String),
+    /// The id and name of a player, sent before the commands which only carry the id of that player.
+    PlayerName(String, String),
 
+    /// The bracket of a tournament as JSON, sent to subscribers whenever it changes.
+    TournamentState(String),
+    /// A finished match as JSON, sent to subscribers.
+    MatchResult(String),
     SubscribeToGames(String),
     Unknown(String),
 }
@@ -41,7 +127,11 @@ impl WriteBytes for Command {
                 let n = opponents.as_slice().write(buffer)?;
                 Ok(b + n)
             },
-            Command::RequestMatch(id) => Self::write_string_with_id(7, id, buffer),
+            Command::RequestMatch(id, options) => {
+                let n = Self::write_string_with_id(7, id, buffer)?;
+                let m = options.write(buffer)?;
+                Ok(n + m)
+            },
             Command::PlayerJoined(id) => Self::write_string_with_id(8, id, buffer),
             Command::Guess(guess) => Self::write_string_with_id(9, guess, buffer),
             Command::Hint(hint) => Self::write_string_with_id(10, hint, buffer),
@@ -50,7 +140,143 @@ impl WriteBytes for Command {
             Command::RequestMatchGuess => Self::write_byte(13, buffer),
             Command::SetGuess(guess) => Self::write_string_with_id(14, guess, buffer),
             Command::PlayerLeft => Self::write_byte(15, buffer),
+            Command::MatchInvite(id, options) => {
+                let n = Self::write_string_with_id(16, id, buffer)?;
+                let m = options.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::AcceptMatch(id) => Self::write_string_with_id(17, id, buffer),
+            Command::DeclineMatch(id) => Self::write_string_with_id(18, id, buffer),
+            Command::CancelMatch(id) => Self::write_string_with_id(19, id, buffer),
+            Command::InviteExpired(id) => Self::write_string_with_id(20, id, buffer),
+            Command::JoinQueue => Self::write_byte(21, buffer),
+            Command::LeaveQueue => Self::write_byte(22, buffer),
+            Command::Reveal(mask) => Self::write_string_with_id(23, mask, buffer),
+            Command::Feedback(guess, feedback) => {
+                let n = Self::write_string_with_id(24, guess, buffer)?;
+                let m = feedback.as_slice().write(buffer)?;
+                Ok(n + m)
+            },
+            Command::GameOver(winner, reason) => {
+                let n = Self::write_string_with_id(25, winner, buffer)?;
+                let m = reason.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::RemainingGuesses(remaining) => {
+                let b = Self::write_byte(26, buffer)?;
+                let n = remaining.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::TimeLeft(seconds) => {
+                let b = Self::write_byte(27, buffer)?;
+                let n = seconds.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::MatchScore(own, opponent) => {
+                let b = Self::write_byte(28, buffer)?;
+                let n = own.write(buffer)?;
+                let m = opponent.write(buffer)?;
+                Ok(b + n + m)
+            },
+            Command::MatchOver(winner) => Self::write_string_with_id(29, winner, buffer),
+            Command::Rematch(swap) => {
+                let b = Self::write_byte(30, buffer)?;
+                let n = swap.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::RematchRequest(id, swap) => {
+                let n = Self::write_string_with_id(31, id, buffer)?;
+                let m = swap.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::CreateRoom(options) => {
+                let b = Self::write_byte(32, buffer)?;
+                let n = options.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::JoinRoom(id) => Self::write_string_with_id(33, id, buffer),
+            Command::LeaveRoom => Self::write_byte(34, buffer),
+            Command::RoomsRequest => Self::write_byte(35, buffer),
+            Command::Rooms(rooms) => {
+                let b = Self::write_byte(36, buffer)?;
+                let n = rooms.as_slice().write(buffer)?;
+                Ok(b + n)
+            },
+            Command::RoomJoined(id) => Self::write_string_with_id(37, id, buffer),
+            Command::RoomMembers(guessers) => {
+                let b = Self::write_byte(38, buffer)?;
+                let n = guessers.as_slice().write(buffer)?;
+                Ok(b + n)
+            },
+            Command::PlayerGuess(id, guess) => {
+                let n = Self::write_string_with_id(39, id, buffer)?;
+                let m = guess.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::RoomRanking(ranking) => {
+                let b = Self::write_byte(40, buffer)?;
+                let n = ranking.as_slice().write(buffer)?;
+                Ok(b + n)
+            },
+            Command::RoomClosed => Self::write_byte(41, buffer),
+            Command::CreateTeams(options) => {
+                let b = Self::write_byte(42, buffer)?;
+                let n = options.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::JoinTeam(id, team) => {
+                let n = Self::write_string_with_id(43, id, buffer)?;
+                let m = team.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::LeaveTeams => Self::write_byte(44, buffer),
+            Command::StartTeams => Self::write_byte(45, buffer),
+            Command::TeamMembers(id, first, second) => {
+                let n = Self::write_string_with_id(46, id, buffer)?;
+                let m = first.as_slice().write(buffer)?;
+                let k = second.as_slice().write(buffer)?;
+                Ok(n + m + k)
+            },
+            Command::TeamChat(message) => Self::write_string_with_id(47, message, buffer),
+            Command::TeamMessage(id, message) => {
+                let n = Self::write_string_with_id(48, id, buffer)?;
+                let m = message.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::TournamentsRequest => Self::write_byte(49, buffer),
+            Command::Tournaments(tournaments) => {
+                let b = Self::write_byte(50, buffer)?;
+                let n = tournaments.as_slice().write(buffer)?;
+                Ok(b + n)
+            },
+            Command::JoinTournament(id) => Self::write_string_with_id(51, id, buffer),
+            Command::LeaveTournament(id) => Self::write_string_with_id(52, id, buffer),
+            Command::Solo(options) => {
+                let b = Self::write_byte(53, buffer)?;
+                let n = options.write(buffer)?;
+                Ok(b + n)
+            },
+            Command::HintRequest => Self::write_byte(54, buffer),
+            Command::SetLanguage(language) => Self::write_string_with_id(55, language, buffer),
+            Command::Register(username, password) => {
+                let n = Self::write_string_with_id(56, username, buffer)?;
+                let m = password.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::Login(username, password) => {
+                let n = Self::write_string_with_id(57, username, buffer)?;
+                let m = password.write(buffer)?;
+                Ok(n + m)
+            },
+            Command::SetName(name) => Self::write_string_with_id(58, name, buffer),
+            Command::PlayerName(id, name) => {
+                let n = Self::write_string_with_id(59, id, buffer)?;
+                let m = name.write(buffer)?;
+                Ok(n + m)
+            },
 
+            Command::TournamentState(json) => Self::write_string_with_id(252, json, buffer),
+            Command::MatchResult(json) => Self::write_string_with_id(253, json, buffer),
             Command::SubscribeToGames(password) => Self::write_string_with_id(254, password, buffer),
             Command::Unknown(message) => Self::write_string_with_id(255, message, buffer),
         }
@@ -99,7 +325,8 @@ impl ReadBytes for Command {
             },
             7 => {
                 let id = String::read(buffer)?;
-                Command::RequestMatch(id)
+                let options = MatchOptions::read(buffer)?;
+                Command::RequestMatch(id, options)
             },
             8 => {
                 let id = String::read(buffer)?;
@@ -121,7 +348,177 @@ impl ReadBytes for Command {
                 Command::SetGuess(guess)
             },
             15 => Command::PlayerLeft,
+            16 => {
+                let id = String::read(buffer)?;
+                let options = MatchOptions::read(buffer)?;
+                Command::MatchInvite(id, options)
+            },
+            17 => {
+                let id = String::read(buffer)?;
+                Command::AcceptMatch(id)
+            },
+            18 => {
+                let id = String::read(buffer)?;
+                Command::DeclineMatch(id)
+            },
+            19 => {
+                let id = String::read(buffer)?;
+                Command::CancelMatch(id)
+            },
+            20 => {
+                let id = String::read(buffer)?;
+                Command::InviteExpired(id)
+            },
+            21 => Command::JoinQueue,
+            22 => Command::LeaveQueue,
+            23 => {
+                let mask = String::read(buffer)?;
+                Command::Reveal(mask)
+            },
+            24 => {
+                let guess = String::read(buffer)?;
+                let feedback = <Vec<LetterFeedback>>::read(buffer)?;
+                Command::Feedback(guess, feedback)
+            },
+            25 => {
+                let winner = String::read(buffer)?;
+                let reason = String::read(buffer)?;
+                Command::GameOver(winner, reason)
+            },
+            26 => {
+                let remaining = u16::read(buffer)?;
+                Command::RemainingGuesses(remaining)
+            },
+            27 => {
+                let seconds = u16::read(buffer)?;
+                Command::TimeLeft(seconds)
+            },
+            28 => {
+                let own = u16::read(buffer)?;
+                let opponent = u16::read(buffer)?;
+                Command::MatchScore(own, opponent)
+            },
+            29 => {
+                let winner = String::read(buffer)?;
+                Command::MatchOver(winner)
+            },
+            30 => {
+                let swap = bool::read(buffer)?;
+                Command::Rematch(swap)
+            },
+            31 => {
+                let id = String::read(buffer)?;
+                let swap = bool::read(buffer)?;
+                Command::RematchRequest(id, swap)
+            },
+            32 => {
+                let options = MatchOptions::read(buffer)?;
+                Command::CreateRoom(options)
+            },
+            33 => {
+                let id = String::read(buffer)?;
+                Command::JoinRoom(id)
+            },
+            34 => Command::LeaveRoom,
+            35 => Command::RoomsRequest,
+            36 => {
+                let rooms = <Vec<String>>::read(buffer)?;
+                Command::Rooms(rooms)
+            },
+            37 => {
+                let id = String::read(buffer)?;
+                Command::RoomJoined(id)
+            },
+            38 => {
+                let guessers = <Vec<String>>::read(buffer)?;
+                Command::RoomMembers(guessers)
+            },
+            39 => {
+                let id = String::read(buffer)?;
+                let guess = String::read(buffer)?;
+                Command::PlayerGuess(id, guess)
+            },
+            40 => {
+                let ranking = <Vec<String>>::read(buffer)?;
+                Command::RoomRanking(ranking)
+            },
+            41 => Command::RoomClosed,
+            42 => {
+                let options = MatchOptions::read(buffer)?;
+                Command::CreateTeams(options)
+            },
+            43 => {
+                let id = String::read(buffer)?;
+                let team = u8::read(buffer)?;
+                Command::JoinTeam(id, team)
+            },
+            44 => Command::LeaveTeams,
+            45 => Command::StartTeams,
+            46 => {
+                let id = String::read(buffer)?;
+                let first = <Vec<String>>::read(buffer)?;
+                let second = <Vec<String>>::read(buffer)?;
+                Command::TeamMembers(id, first, second)
+            },
+            47 => {
+                let message = String::read(buffer)?;
+                Command::TeamChat(message)
+            },
+            48 => {
+                let id = String::read(buffer)?;
+                let message = String::read(buffer)?;
+                Command::TeamMessage(id, message)
+            },
+            49 => Command::TournamentsRequest,
+            50 => {
+                let tournaments = <Vec<String>>::read(buffer)?;
+                Command::Tournaments(tournaments)
+            },
+            51 => {
+                let id = String::read(buffer)?;
+                Command::JoinTournament(id)
+            },
+            52 => {
+                let id = String::read(buffer)?;
+                Command::LeaveTournament(id)
+            },
+            53 => {
+                let options = MatchOptions::read(buffer)?;
+                Command::Solo(options)
+            },
+            54 => Command::HintRequest,
+            55 => {
+                let language = String::read(buffer)?;
+                Command::SetLanguage(language)
+            },
+            56 => {
+                let username = String::read(buffer)?;
+                let password = String::read(buffer)?;
+                Command::Register(username, password)
+            },
+            57 => {
+                let username = String::read(buffer)?;
+                let password = String::read(buffer)?;
+                Command::Login(username, password)
+            },
+            58 => {
+                let name = String::read(buffer)?;
+                Command::SetName(name)
+            },
+            59 => {
+                let id = String::read(buffer)?;
+                let name = String::read(buffer)?;
+                Command::PlayerName(id, name)
+            },
 
+            252 => {
+                let json = String::read(buffer)?;
+                Command::TournamentState(json)
+            },
+            253 => {
+                let json = String::read(buffer)?;
+                Command::MatchResult(json)
+            },
             254 => {
                 let password = String::read(buffer)?;
                 Command::SubscribeToGames(password)
@@ -38,7 +38,7 @@ impl ReadBytes for String {
     fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
         let length = u16::read(buffer)?;
         let bytes = buffer.take(length as usize)
-            .map(|b| *b).collect::<Vec<_>>();
+            .copied().collect::<Vec<_>>();
 
         let string = String::from_utf8_lossy(&bytes);
         Some(string.to_string())
@@ -69,6 +69,13 @@ impl<T: ReadBytes> ReadBytes for Vec<T> {
     }
 }
 
+impl WriteBytes for u8 {
+    fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
+        buffer.write_all(&[*self])?;
+        Ok(1)
+    }
+}
+
 impl ReadBytes for u8 {
     fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
         let n = *buffer.next()?;
@@ -78,7 +85,7 @@ impl ReadBytes for u8 {
 
 impl WriteBytes for u16 {
     fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
-        buffer.write(&self.to_be_bytes())?;
+        buffer.write_all(&self.to_be_bytes())?;
         Ok(2)
     }
 }
@@ -93,6 +100,42 @@ impl ReadBytes for u16 {
     }
 }
 
+impl WriteBytes for bool {
+    fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
+        (*self as u8).write(buffer)
+    }
+}
+
+impl ReadBytes for bool {
+    fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
+        match u8::read(buffer)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// A presence byte followed by the value if it is set.
+impl<T: WriteBytes> WriteBytes for Option<T> {
+    fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
+        match self {
+            Some(value) => Ok(1u8.write(buffer)? + value.write(buffer)?),
+            None => 0u8.write(buffer),
+        }
+    }
+}
+
+impl<T: ReadBytes> ReadBytes for Option<T> {
+    fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
+        match u8::read(buffer)? {
+            0 => Some(None),
+            1 => Some(Some(T::read(buffer)?)),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,286 @@
+use std::{collections::HashMap, sync::Arc};
+
+use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppResult, Command};
+
+use super::{get_timestamp, AMPlayer, ARWServerState, History, Server, ServerState};
+
+/// A registered player, the password is only stored as a salted argon2 hash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Account {
+    pub(super) username: String,
+    /// The hash in PHC string format, which includes the salt and the parameters.
+    password_hash: String,
+    created: u64,
+}
+
+/// All accounts by their username in lowercase, so no two usernames differ only in case.
+#[derive(Default)]
+pub struct Accounts {
+    accounts: HashMap<String, Account>,
+    /// Where new accounts are appended, `None` if they are only kept in memory.
+    file: Option<History>,
+}
+
+impl Accounts {
+    const MIN_PASSWORD_LENGTH: usize = 8;
+
+    /// Read the accounts stored in the file and open it for new ones.
+    pub fn load(path: &str) -> AppResult<Self> {
+        let accounts = History::load::<Account>(path)?.into_iter()
+            .map(|account| (account.username.to_lowercase(), account))
+            .collect();
+
+        Ok(Self {
+            accounts,
+            file: Some(History::open(path)?),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Check the username and password of a new account, the error says what is wrong with them.
+    pub fn validate(&self, username: &str, password: &str) -> Result<(), String> {
+        validate_username(username)?;
+        if password.chars().count() < Self::MIN_PASSWORD_LENGTH {
+            return Err(format!("The password needs at least {} characters", Self::MIN_PASSWORD_LENGTH))
+        }
+        if self.accounts.contains_key(&username.to_lowercase()) {
+            return Err(format!("The username {:?} is taken", username))
+        }
+
+        Ok(())
+    }
+
+    /// Store a new account, the username has to be validated and the password hashed before.
+    pub fn insert(&mut self, account: Account) -> AppResult<()> {
+        if let Some(file) = &self.file {
+            file.append(&account)?;
+        }
+
+        self.accounts.insert(account.username.to_lowercase(), account);
+        Ok(())
+    }
+
+    /// The account with the username in any case.
+    pub fn get(&self, username: &str) -> Option<&Account> {
+        self.accounts.get(&username.to_lowercase())
+    }
+}
+
+impl Account {
+    /// Create an account with a random salt, hashing takes a moment, so no lock should be held.
+    pub fn new(username: String, password: &str) -> AppResult<Self> {
+        let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(AppError::Password)?;
+        let password_hash = Argon2::default().hash_password(password.as_bytes(), &salt).map_err(AppError::Password)?;
+
+        Ok(Self {
+            username,
+            password_hash: password_hash.to_string(),
+            created: get_timestamp(),
+        })
+    }
+
+    /// Whether the password is the one the account was created with, like hashing this takes a moment.
+    pub fn verify(&self, password: &str) -> bool {
+        PasswordHash::new(&self.password_hash)
+            .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+    }
+}
+
+/// Usernames have 3 to 20 letters, digits, `_` or `-`.
+fn validate_username(username: &str) -> Result<(), String> {
+    let length = username.chars().count();
+    if !(3..=20).contains(&length) {
+        return Err("A username needs 3 to 20 characters".to_string())
+    }
+    if !username.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return Err("A username can only have letters, digits, _ and -".to_string())
+    }
+
+    Ok(())
+}
+
+/// Names have 2 to 20 letters, digits, spaces, `_`, `-` or `.`, with at least one letter so they cannot be mistaken for an id.
+fn validate_name(name: &str) -> Result<(), String> {
+    let length = name.chars().count();
+    if !(2..=20).contains(&length) {
+        return Err("A name needs 2 to 20 characters".to_string())
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.')) {
+        return Err("A name can only have letters, digits, spaces, _, - and .".to_string())
+    }
+    if name.contains("  ") || name.trim() != name {
+        return Err("A name cannot have spaces in a row or around it".to_string())
+    }
+    if !name.chars().any(char::is_alphabetic) {
+        return Err("A name needs at least one letter".to_string())
+    }
+
+    Ok(())
+}
+
+impl Server {
+    /// Refused logins and registrations after which the connection is closed, so passwords cannot be tried one after another.
+    pub(super) const MAX_LOGIN_ATTEMPTS: u8 = 3;
+
+    /// Create an account and log the player in with it.
+    pub(super) fn register(player: &AMPlayer, state: &ARWServerState, username: String, password: String) -> AppResult<()> {
+        println!("Registering account {:?}", username);
+        if let Err(err) = state.read().unwrap().accounts.validate(&username, &password) {
+            return Self::refuse_login(player, err)
+        }
+
+        let account = Account::new(username, &password)?;
+
+        {
+            let mut state = state.write().unwrap();
+            // someone else could have taken the username while hashing
+            if let Err(err) = state.accounts.validate(&account.username, &password) {
+                drop(state);
+                return Self::refuse_login(player, err)
+            }
+            // checked under the same lock as names are set, so no guest can take it in between
+            if Self::name_taken(&state, player, &account.username) {
+                drop(state);
+                return Self::refuse_login(player, format!("The username {:?} is taken", account.username))
+            }
+            state.accounts.insert(account.clone())?;
+        }
+
+        Self::log_in(player, state, account.username)
+    }
+
+    /// Log the player in with an existing account, an account can only be logged in once at a time.
+    pub(super) fn login(player: &AMPlayer, state: &ARWServerState, username: String, password: String) -> AppResult<()> {
+        println!("Logging in account {:?}", username);
+        let account = state.read().unwrap().accounts.get(&username).cloned();
+        let Some(account) = account.filter(|account| account.verify(&password)) else {
+            return Self::refuse_login(player, "Wrong username or password")
+        };
+
+        Self::log_in(player, state, account.username)
+    }
+
+    /// Mark the player as logged in with the account, checked and set under the same lock so two connections cannot both get in.
+    fn log_in(player: &AMPlayer, state: &ARWServerState, username: String) -> AppResult<()> {
+        let state = state.write().unwrap();
+        let logged_in = state.players.values()
+            .filter(|other| !Arc::ptr_eq(other, player))
+            .any(|other| other.read().unwrap().account.as_ref() == Some(&username));
+        if logged_in {
+            drop(state);
+            return Self::refuse_login(player, "The account is already logged in")
+        }
+
+        let mut player = player.write().unwrap();
+        player.authenticated = true;
+        player.account = Some(username);
+        let id = player.id.to_string();
+        Self::send(&mut player.connection, Command::SetId(id))
+    }
+
+    /// Set the name the player is shown with, no two players can have the same name in any case and nobody can take the username of another account.
+    pub(super) fn set_name(player: &AMPlayer, state: &ARWServerState, name: String) -> AppResult<()> {
+        let name = name.trim();
+        if !name.is_empty() {
+            if let Err(err) = validate_name(name) {
+                return Self::send_error(player, err)
+            }
+
+        }
+
+        // checked and set under the same lock, so two players cannot take the same name at once
+        let state = state.write().unwrap();
+        if !name.is_empty() {
+            let account = player.read().unwrap().account.clone();
+            let username = state.accounts.get(name).is_some_and(|other| Some(&other.username) != account.as_ref());
+
+            if username || Self::name_taken(&state, player, name) {
+                drop(state);
+                return Self::send_error(player, format!("The name {:?} is taken", name))
+            }
+        }
+
+        let mut player = player.write().unwrap();
+        player.name = (!name.is_empty()).then(|| name.to_string());
+        Self::send(&mut player.connection, Command::Ok)
+    }
+
+    /// Whether another connected player is shown with the name in any case, a disconnected player frees their name.
+    fn name_taken(state: &ServerState, player: &AMPlayer, name: &str) -> bool {
+        let name = name.to_lowercase();
+        state.players.values()
+            .filter(|other| !Arc::ptr_eq(other, player))
+            .any(|other| {
+                let other = other.read().unwrap();
+                other.connected && other.display_name().is_some_and(|other| other.to_lowercase() == name)
+            })
+    }
+
+    /// Send the names of the players to another one before a command with their ids, players without a name are left out.
+    /// No player may be locked by the caller.
+    pub(super) fn send_names(state: &ServerState, to: u32, ids: impl IntoIterator<Item = u32>) -> AppResult<()> {
+        for (id, name) in state.names(ids) {
+            Self::send_to(state, to, Command::PlayerName(id.to_string(), name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Tell the player why they could not log in and ask again, after too many attempts the connection is closed instead.
+    fn refuse_login(player: &AMPlayer, err: impl Into<String>) -> AppResult<()> {
+        let mut player = player.write().unwrap();
+        Self::send(&mut player.connection, Command::Error(err.into()))?;
+
+        player.failed_logins = player.failed_logins.saturating_add(1);
+        if player.failed_logins >= Self::MAX_LOGIN_ATTEMPTS {
+            return Err(AppError::InvalidAuth)
+        }
+
+        Self::send(&mut player.connection, Command::PasswordRequest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts() {
+        let mut accounts = Accounts::default();
+        assert_eq!(accounts.validate("ab", "password"), Err("A username needs 3 to 20 characters".to_string()));
+        assert!(accounts.validate("jean paul", "password").is_err());
+        assert!(accounts.validate("Léa_2", "short").is_err());
+        assert_eq!(accounts.validate("Léa_2", "password"), Ok(()));
+
+        let account = Account::new("Léa_2".to_string(), "password").unwrap();
+        assert!(account.password_hash.starts_with("$argon2id$"));
+        assert!(!account.password_hash.contains("password"));
+        accounts.insert(account).unwrap();
+
+        assert!(accounts.validate("LÉA_2", "password").is_err());
+        assert!(accounts.get("léa_2").is_some_and(|account| account.verify("password")));
+        assert!(!accounts.get("Léa_2").unwrap().verify("Password"));
+        assert!(accounts.get("Lea_2").is_none());
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert_eq!(validate_name("Léa M."), Ok(()));
+        assert_eq!(validate_name("Bot 12"), Ok(()));
+        assert!(validate_name("L").is_err());
+        assert!(validate_name("12").is_err());
+        assert!(validate_name("Léa  M").is_err());
+        assert!(validate_name(" Léa").is_err());
+        assert!(validate_name("<b>Léa</b>").is_err());
+    }
+}
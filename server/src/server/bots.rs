@@ -0,0 +1,270 @@
+use std::{collections::{HashSet, VecDeque}, os::unix::net::UnixStream, sync::{mpsc, Arc}, thread, time::{Duration, Instant}};
+
+use rand::seq::IteratorRandom;
+
+use crate::{AppResult, Command, Connection};
+
+use super::{graphemes, solo_hint, ARWServerState, Equivalence, LetterFeedback, MatchOptions, Server, ServerConfig, WordList};
+
+/// What a bot is doing in its current game.
+enum BotRole {
+    Idle,
+    /// The hints not given yet, one follows every guess.
+    Hinting(VecDeque<String>),
+    Guessing {
+        mask: String,
+        feedback: Vec<(String, Vec<LetterFeedback>)>,
+        guessed: HashSet<String>,
+    },
+}
+
+/// A player run by the server, it talks to the server over its own connection like any client.
+/// It accepts every invite and plays whichever role it gets, as hinter it picks a word from the hint list and as guesser it guesses from the word list of the match language.
+struct Bot {
+    id: String,
+    connection: Connection,
+    config: Arc<ServerConfig>,
+    /// Options of the last accepted invite, the defaults for matches the bot was not invited to.
+    options: MatchOptions,
+    role: BotRole,
+    next_guess: Option<Instant>,
+}
+
+impl Bot {
+    /// Time a bot waits before each guess.
+    const THINK_TIME: Duration = Duration::from_secs(2);
+
+    fn new(connection: Connection, config: Arc<ServerConfig>) -> Self {
+        Self {
+            id: String::new(),
+            connection,
+            config,
+            options: MatchOptions::default(),
+            role: BotRole::Idle,
+            next_guess: None,
+        }
+    }
+
+    /// Handle commands until the server closes the connection, a guess is made whenever one is due.
+    fn run(mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let reader = self.connection.reader.clone();
+        thread::spawn(move || {
+            let mut lock = reader.lock().unwrap();
+            while let Ok(command) = Server::receive(lock.reader()) {
+                if sender.send(command).is_err() {
+                    break
+                }
+            }
+        });
+
+        loop {
+            let timeout = self.next_guess.map_or(Duration::MAX, |due| due.saturating_duration_since(Instant::now()));
+            let result = match receiver.recv_timeout(timeout) {
+                Ok(command) => self.handle(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => self.guess(),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+
+            if let Err(err) = result {
+                eprintln!("Bot {} stopped: {:?}", self.id, err);
+                break
+            }
+        }
+    }
+
+    fn send(&mut self, command: Command) -> AppResult<()> {
+        Server::send(&mut self.connection, command)
+    }
+
+    /// Give the next hint as hinter, one follows every guess until they run out.
+    fn hint(&mut self) -> AppResult<()> {
+        if let BotRole::Hinting(hints) = &mut self.role {
+            if let Some(hint) = hints.pop_front() {
+                self.send(Command::Hint(hint))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, command: Command) -> AppResult<()> {
+        match command {
+            Command::PasswordRequest => {
+                let password = self.config.password.clone();
+                self.send(Command::Password(password))?;
+            },
+            Command::SetId(id) => {
+                self.send(Command::SetName(format!("Bot {}", id)))?;
+                self.id = id;
+            },
+            Command::MatchInvite(id, options) => match self.role {
+                BotRole::Idle => {
+                    self.options = options;
+                    self.send(Command::AcceptMatch(id))?;
+                },
+                _ => self.send(Command::DeclineMatch(id))?,
+            },
+            Command::RematchRequest(_, _) => self.send(Command::Rematch(false))?,
+            Command::RequestMatchGuess => self.set_word()?,
+            Command::Guess(_) | Command::PlayerGuess(_, _) => self.hint()?,
+            Command::SetGuess(mask) => {
+                self.role = BotRole::Guessing {
+                    mask,
+                    feedback: Vec::new(),
+                    guessed: HashSet::new(),
+                };
+                self.next_guess = Some(Instant::now() + Self::THINK_TIME);
+            },
+            Command::Reveal(revealed) => {
+                if let BotRole::Guessing { mask, .. } = &mut self.role {
+                    *mask = revealed;
+                }
+            },
+            // a hinter in wordle sees the feedback instead of the guess
+            Command::Feedback(guess, letters) => match &mut self.role {
+                BotRole::Guessing { feedback, .. } => feedback.push((guess, letters)),
+                BotRole::Hinting(_) => self.hint()?,
+                BotRole::Idle => {},
+            },
+            Command::Win | Command::GameOver(_, _) | Command::PlayerLeft | Command::Surrender => {
+                self.role = BotRole::Idle;
+                self.next_guess = None;
+            },
+            Command::Error(message) => eprintln!("Bot {} was sent an error: {}", self.id, message),
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    /// Pick a word with hints from the hint list, words from the word list get the hints of solo games.
+    fn set_word(&mut self) -> AppResult<()> {
+        let hints = self.config.hints.get(&self.options.language);
+        let picked = hints.and_then(|hints| hints.pick(|word| self.config.accepts(&self.options, word))).or_else(|| {
+            let word = self.config.pick_word(&self.options)?;
+            let hints = (0..).map_while(|given| solo_hint(&word, given)).collect();
+            Some((word, hints))
+        });
+
+        let Some((word, hints)) = picked else {
+            eprintln!("Bot {} found no word for {}", self.id, self.options);
+            return self.send(Command::Surrender)
+        };
+
+        let mut hints = VecDeque::from(hints);
+        if let Some(limit) = self.options.hint_limit {
+            hints.truncate(limit as usize);
+        }
+
+        self.send(Command::SetGuess(word))?;
+        if let Some(hint) = hints.pop_front() {
+            self.send(Command::Hint(hint))?;
+        }
+
+        self.role = BotRole::Hinting(hints);
+        Ok(())
+    }
+
+    /// Guess a random word which fits everything seen so far, the bot gives up once there is none.
+    fn guess(&mut self) -> AppResult<()> {
+        self.next_guess = None;
+        let BotRole::Guessing { mask, feedback, guessed } = &mut self.role else {
+            return Ok(())
+        };
+
+        let equivalence = &self.config.equivalence;
+        let candidate = self.config.words.get(&self.options.language).into_iter().flat_map(WordList::iter)
+            .filter(|word| !guessed.contains(*word) && fits(word, mask, feedback, equivalence))
+            .choose(&mut rand::rng())
+            .map(str::to_string);
+
+        let Some(word) = candidate else {
+            self.role = BotRole::Idle;
+            return self.send(Command::Surrender)
+        };
+
+        guessed.insert(word.clone());
+        self.next_guess = Some(Instant::now() + Self::THINK_TIME);
+        self.send(Command::Guess(word))
+    }
+}
+
+/// Whether the word has the letters of the mask, where `_` stands for a hidden letter, and matches the feedback on every earlier guess.
+fn fits(word: &str, mask: &str, feedback: &[(String, Vec<LetterFeedback>)], equivalence: &Equivalence) -> bool {
+    let letters = graphemes(word);
+    let mask = graphemes(mask);
+    if letters.len() != mask.len() {
+        return false
+    }
+
+    let same = |a: &str, b: &str| equivalence.matches(a, b);
+    if letters.iter().zip(&mask).any(|(letter, shown)| *shown != "_" && !same(letter, shown)) {
+        return false
+    }
+
+    feedback.iter().all(|(guess, verdicts)| {
+        let guess = graphemes(guess);
+        guess.len() == letters.len() && guess.iter().zip(verdicts).enumerate().all(|(i, (g, verdict))| match verdict {
+            LetterFeedback::Correct => same(letters[i], g),
+            LetterFeedback::Present => !same(letters[i], g) && letters.iter().any(|letter| same(letter, g)),
+            // a letter guessed twice can be absent once and still be in the word
+            LetterFeedback::Absent => {
+                let found = guess.iter().zip(verdicts).any(|(other, verdict)| *verdict != LetterFeedback::Absent && same(other, g));
+                if found {
+                    !same(letters[i], g)
+                } else {
+                    !letters.iter().any(|letter| same(letter, g))
+                }
+            },
+        })
+    })
+}
+
+impl Server {
+    /// Add a bot player, it shows up as an opponent once it answered the password request.
+    pub(super) fn add_bot(state: &ARWServerState, config: &Arc<ServerConfig>) -> AppResult<u32> {
+        let (server_end, bot_end) = UnixStream::pair()?;
+        let player = state.write().unwrap().create_player(Connection::unix(server_end)?);
+        let id = player.read().unwrap().id;
+
+        let bot = Bot::new(Connection::unix(bot_end)?, config.clone());
+        thread::spawn(move || bot.run());
+
+        let state = state.clone();
+        let config = config.clone();
+        thread::spawn(move || {
+            if let Err(err) = Self::handle_client(player, state.clone(), config) {
+                eprintln!("Bot {} disconnected: {:?}", id, err);
+            }
+
+            state.write().unwrap().players.remove(&id);
+        });
+
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        use LetterFeedback::*;
+        let equivalence = Equivalence::default();
+
+        assert!(fits("Kaffi", "K___i", &[], &equivalence));
+        assert!(fits("Kaffi", "_____", &[], &equivalence));
+        assert!(!fits("Kaffi", "____", &[], &equivalence));
+        assert!(!fits("Kaffi", "B____", &[], &equivalence));
+
+        let feedback = [("kees".to_string(), vec![Correct, Absent, Absent, Correct])];
+        assert!(fits("Kéis", "____", &feedback, &equivalence));
+        assert!(!fits("Kaes", "____", &feedback, &equivalence));
+
+        let feedback = [("babes".to_string(), vec![Present, Present, Correct, Correct, Absent])];
+        assert!(fits("abbey", "_____", &feedback, &equivalence));
+        assert!(!fits("abbes", "_____", &feedback, &equivalence));
+    }
+}
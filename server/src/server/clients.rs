@@ -1,16 +1,12 @@
-use std::{sync::{Arc, RwLock}, time::{SystemTime, UNIX_EPOCH}};
+use std::sync::Arc;
 
 use crate::{AppError, AppResult, Command, Connection};
 
-use super::{AMPlayer, ARWServerState, Game, Player, Server, ServerConfig, ServerState};
-
-fn get_timestamp() -> u64 {
-    SystemTime::now().duration_since(UNIX_EPOCH).expect("time travel").as_secs()
-}
+use super::{get_timestamp, shares_language, AMGame, AMPlayer, ARWServerState, EndReason, Game, MatchOptions, Server, ServerConfig, ServerState};
 
 impl Server {
     fn broadcast_games(state: &ServerState, game: &Game) -> AppResult<()> {
-        if !game.finished || game.word.is_none() {
+        if !game.is_finished() || game.word.is_none() {
             return Ok(())
         }
 
@@ -18,8 +14,8 @@ impl Server {
         
         for (_, conns) in subs.iter_mut() {
             if let Some(conn) = conns {
-                let json = serde_json::to_string(game).map_err(|e| AppError::Serde(e))?;
-                if let Err(_) = Self::send(conn, Command::SubscribeToGames(json)) {
+                let json = serde_json::to_string(game).map_err(AppError::Serde)?;
+                if Self::send(conn, Command::SubscribeToGames(json)).is_err() {
                     *conns = None;
                 };
             }
@@ -29,53 +25,169 @@ impl Server {
     }
 
     fn broadcast_games_single(connection: &mut Connection, game: &Game) -> AppResult<()> {
-        if !game.finished || game.word.is_none() {
+        if !game.is_finished() || game.word.is_none() {
             return Ok(())
         }
-        let json = serde_json::to_string(game).map_err(|e| AppError::Serde(e))?;
+        let json = serde_json::to_string(game).map_err(AppError::Serde)?;
         Self::send(connection, Command::SubscribeToGames(json))
     }
 
     fn disconnect(player: AMPlayer, state: ARWServerState) -> AppResult<()> {
-        let in_game = player.read().unwrap().in_game;
+        let id = {
+            let mut player = player.write().unwrap();
+            player.connected = false;
+            player.id
+        };
+        state.write().unwrap().matchmaker.leave(id);
+        Self::clear_invites(id, &state)?;
+        Self::leave_room(&player, &state, EndReason::Disconnected)?;
+        Self::leave_teams(&player, &state)?;
+        Self::forfeit(&player, &state, Command::PlayerLeft, EndReason::Disconnected)
+    }
 
-        let Some(game_id) = in_game else {
-            return Ok(())
+    /// End the player's current game in favour of the opponent, who is notified with `notice` unless they are the hinter of a room.
+    /// In a team game the whole team loses and everyone is told with [`Command::GameOver`].
+    pub(super) fn forfeit(player: &AMPlayer, state: &ARWServerState, notice: Command, reason: EndReason) -> AppResult<()> {
+        let (self_id, in_game) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_game)
         };
 
-        let state = state.read().unwrap();
-        let Some(game) = state.games.get(&game_id) else {
+        let Some(game_id) = in_game else {
             return Ok(())
         };
 
-        let player_id = {
+        {
+            let state = state.read().unwrap();
+            let Some(game) = state.games.get(&game_id) else {
+                return Ok(())
+            };
+
             let mut game = game.write().unwrap();
-            game.finished = true;
-            game.timestamp = get_timestamp();
+            let opponent_id = game.opponent_of(self_id);
 
-            let guesser = game.guesser != player.read().unwrap().id;
+            if game.teams.is_empty() {
+                game.finish(Some(opponent_id), reason)?;
+                Self::broadcast_games(&state, &game)?;
+                let in_room = game.room_id.is_some();
+                drop(game);
 
-            let player_id = if guesser {
-                game.winner = Some(game.guesser);
-                game.guesser
+                player.write().unwrap().in_game = None;
+
+                if let Some(opponent) = state.players.get(&opponent_id).filter(|_| !in_room) {
+                    let mut opponent = opponent.write().unwrap();
+                    opponent.in_game = None;
+                    Self::send(&mut opponent.connection, notice)?;
+                }
             } else {
-                game.winner = Some(game.hinter);
-                game.hinter
+                // the whole team loses, teammates and opponents alike are told who won
+                Self::end_game(&state, &mut game, Some(opponent_id), reason)?;
+            }
+        }
+
+        Self::finish_round(state, game_id)
+    }
+
+    /// Finish the game and notify both players, a guessed word is announced with [`Command::Win`], any other ending with [`Command::GameOver`].
+    pub(super) fn end_game(state: &ServerState, game: &mut Game, winner: Option<u32>, reason: EndReason) -> AppResult<()> {
+        game.finish(winner, reason)?;
+        Self::broadcast_games(state, game)?;
+
+        let notice = match reason {
+            EndReason::Guessed => Command::Win,
+            _ => Command::GameOver(winner.map(|id| id.to_string()).unwrap_or_default(), reason.to_string()),
+        };
+
+        for player_id in game.notified_players() {
+            let Some(player) = state.players.get(&player_id) else {
+                continue
             };
 
-            Self::broadcast_games(&state, &game)?;
+            // one player leaving must not keep the others from being told
+            let mut player = player.write().unwrap();
+            player.in_game = None;
+            if let Err(err) = Self::send(&mut player.connection, notice.clone()) {
+                eprintln!("Failed to notify player {}: {:?}", player_id, err);
+            }
+        }
 
-            player_id
+        Ok(())
+    }
+
+    /// End the game if a deadline passed before the timers noticed, returns whether it did.
+    fn end_if_timed_out(state: &ServerState, game: &mut Game) -> AppResult<bool> {
+        let Some(winner) = game.timeout_winner(get_timestamp()) else {
+            return Ok(false)
         };
 
+        Self::end_game(state, game, Some(winner), EndReason::TimedOut)?;
+        Ok(true)
+    }
+
+    /// End a game from the server console, both players are sent back to the menu.
+    pub(super) fn abort_game(state: &ARWServerState, game_id: u32) -> AppResult<()> {
+        {
+            let state = state.read().unwrap();
+            let Some(game) = state.games.get(&game_id) else {
+                println!("Game {} not found", game_id);
+                return Ok(())
+            };
+
+            let mut game = game.write().unwrap();
+            if let Err(err) = game.finish(None, EndReason::AbortedByAdmin) {
+                println!("Cannot abort game {}: {}", game_id, err);
+                return Ok(())
+            }
+            Self::broadcast_games(&state, &game)?;
+
+            for player_id in game.notified_players() {
+                let Some(player) = state.players.get(&player_id) else {
+                    continue
+                };
+
+                let mut player = player.write().unwrap();
+                player.in_game = None;
+                if let Err(err) = Self::send(&mut player.connection, Command::Error("Game aborted by admin".to_string())) {
+                    eprintln!("Failed to notify player {}: {:?}", player_id, err);
+                }
+            }
+        }
+
+        println!("Aborted game {}", game_id);
+        Self::finish_round(state, game_id)
+    }
+
+    /// Set the language the player wants to play in, the opponents they are shown and matched with have to share it.
+    /// An empty language accepts any.
+    fn set_language(player: &AMPlayer, language: String) -> AppResult<()> {
+        if !language.is_empty() && !MatchOptions::LANGUAGES.contains(&language.as_str()) {
+            return Self::send_error(player, format!("Unknown language, available: {}", MatchOptions::LANGUAGES.join(", ")))
+        }
+
+        let mut player = player.write().unwrap();
+        player.language = (!language.is_empty()).then_some(language);
+        Self::send(&mut player.connection, Command::Ok)
+    }
+
+    /// Send an error message to the player.
+    pub(super) fn send_error(player: &AMPlayer, message: impl Into<String>) -> AppResult<()> {
+        Self::send(&mut player.write().unwrap().connection, Command::Error(message.into()))
+    }
+
+    /// Send a command to the player with the given id, does nothing if the player is not connected.
+    pub(super) fn send_to(state: &ServerState, player_id: u32, command: Command) -> AppResult<()> {
         let Some(player) = state.players.get(&player_id) else {
             return Ok(())
         };
 
-        let mut player = player.write().unwrap();
-        player.in_game = None;
-        Self::send(&mut player.connection, Command::PlayerLeft)?;
-        Ok(())
+        Self::send(&mut player.write().unwrap().connection, command)
+    }
+
+    /// Send a command to another player than the one being handled, a failure is only logged as it is their connection which broke.
+    pub(super) fn notify(state: &ServerState, player_id: u32, command: Command) {
+        if let Err(err) = Self::send_to(state, player_id, command) {
+            eprintln!("Failed to notify player {}: {:?}", player_id, err);
+        }
     }
 
     fn handle_client_auth(player: &AMPlayer) -> AppResult<()> {
@@ -107,9 +219,20 @@ impl Server {
                     return Ok(())
                 }
             };
-            Self::handle_command(command, &player, &state, &config)?;
+            match Self::handle_command(command, &player, &state, &config) {
+                // commands which are illegal in the current game phase are reported back to the client
+                Err(AppError::Game(err)) => Self::send(&mut player.write().unwrap().connection, Command::Error(err.to_string()))?,
+                // any other error closes the connection, which is cleaned up like a disconnect
+                Err(err) => {
+                    Self::disconnect(player, state)?;
+                    return Err(err)
+                },
+                Ok(()) => {},
+            }
 
-            if !player.read().unwrap().authenticated {
+            // a refused login is asked again, a subscriber is handled below
+            let authenticated = player.read().unwrap().authenticated;
+            if !authenticated && state.read().unwrap().subscribers.lock().unwrap().contains_key(&id) {
                 break
             }
         };
@@ -125,9 +248,20 @@ impl Server {
             let Ok(mut player) = player.into_inner() else { return Ok(()) };
 
             println!("Added new subscriber");
-            for (_, game) in &state.games {
+            // only the games the retention keeps, oldest first
+            let mut games = state.games.values().collect::<Vec<_>>();
+            games.sort_by_key(|game| game.read().unwrap().id);
+            for game in games {
                 Self::broadcast_games_single(&mut player.connection, &game.read().unwrap())?;
             }
+            for series in state.matches.values().filter(|series| series.is_finished()) {
+                let json = serde_json::to_string(series).map_err(AppError::Serde)?;
+                Self::send(&mut player.connection, Command::MatchResult(json))?;
+            }
+            for tournament in state.tournaments.values() {
+                let json = serde_json::to_string(tournament).map_err(AppError::Serde)?;
+                Self::send(&mut player.connection, Command::TournamentState(json))?;
+            }
 
             state.subscribers.lock().unwrap().get_mut(&id).unwrap().replace(player.connection);
         }
@@ -136,42 +270,45 @@ impl Server {
     }
 
     pub fn handle_command(command: Command, player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>) -> AppResult<()> {
-        match command {
-            Command::SubscribeToGames(password) => {
-                println!("Received password: {:?}", password);
+        if let Command::SubscribeToGames(password) = command {
+            println!("Received password: {:?}", password);
 
-                if password == config.password {
-                    let id = player.read().unwrap().id;
-                    let state = state.read().unwrap();
-                    state.subscribers.lock().unwrap().insert(id, None);
-                    return Ok(())
-                } else {
-                    return Err(AppError::InvalidAuth);
-                }
-            },
-            _ => (),
-        };
+            if password == config.password {
+                let id = player.read().unwrap().id;
+                let state = state.read().unwrap();
+                state.subscribers.lock().unwrap().insert(id, None);
+                return Ok(())
+            } else {
+                return Err(AppError::InvalidAuth);
+            }
+        }
 
         if !player.read().unwrap().authenticated {
-            if let Command::Password(pass) = command {
-                println!("Received password: {:?}", pass);
-
-                if pass == config.password {
-                    let mut player = player.write().unwrap();
-                    player.authenticated = true;
-                    let id = player.id.to_string();
-                    return Self::send(&mut player.connection, Command::SetId(id));
-                } else {
-                    return Err(AppError::InvalidAuth);
-                }
-            } else {
-                return Err(AppError::Unauthorized);
+            match command {
+                Command::Password(pass) => {
+                    println!("Received password: {:?}", pass);
+
+                    if pass == config.password {
+                        let mut player = player.write().unwrap();
+                        player.authenticated = true;
+                        let id = player.id.to_string();
+                        return Self::send(&mut player.connection, Command::SetId(id));
+                    } else {
+                        return Err(AppError::InvalidAuth);
+                    }
+                },
+                Command::Register(username, password) => return Self::register(player, state, username, password),
+                Command::Login(username, password) => return Self::login(player, state, username, password),
+                _ => return Err(AppError::Unauthorized),
             }
         }
 
         match command {
             Command::OpponentsRequest => {
-                let self_id = player.read().unwrap().id;
+                let (self_id, language) = {
+                    let player = player.read().unwrap();
+                    (player.id, player.language.clone())
+                };
 
                 let state = state.read().unwrap();
                 let players = state.players.iter().filter_map(|(id, player)| {
@@ -180,125 +317,116 @@ impl Server {
                     }
 
                     let player = player.read().unwrap();
-                    if player.in_game.is_none() && player.authenticated {
-                        Some(player.id.to_string())
+                    if !player.is_busy() && player.authenticated && shares_language(&language, &player.language) {
+                        Some(player.id)
                     } else {
                         None
                     }
                 }).collect::<Vec<_>>();
 
+                Self::send_names(&state, self_id, players.iter().copied())?;
+                let players = players.iter().map(u32::to_string).collect();
                 let mut player_self = player.write().unwrap();
                 Self::send(&mut player_self.connection, Command::Opponents(players))?;
             },
-            Command::RequestMatch(id) => {
-                let self_id = player.read().unwrap().id;
-                let Ok(player_id) = id.parse() else {
-                    Self::send(&mut player.write().unwrap().connection, Command::Error("Invalid player id".to_string()))?;
+            Command::RequestMatch(id, options) => Self::request_match(player, state, id, options)?,
+            Command::AcceptMatch(id) => Self::accept_match(player, state, config, id)?,
+            Command::DeclineMatch(id) => Self::decline_match(player, state, id)?,
+            Command::CancelMatch(id) => Self::cancel_match(player, state, id)?,
+            Command::JoinQueue => Self::join_queue(player, state, config)?,
+            Command::LeaveQueue => Self::leave_queue(player, state)?,
+            Command::CreateRoom(options) => Self::create_room(player, state, config, options)?,
+            Command::JoinRoom(id) => Self::join_room(player, state, id)?,
+            Command::LeaveRoom => Self::leave_room(player, state, EndReason::Surrendered)?,
+            Command::RoomsRequest => Self::list_rooms(player, state)?,
+            Command::SetGuess(guess) if player.read().unwrap().in_room.is_some() => Self::set_room_word(player, state, config, guess)?,
+            Command::Hint(hint) if player.read().unwrap().in_room.is_some() => Self::room_hint(player, state, hint)?,
+            Command::SetGuess(guess) => {
+                let Some((game, other_player)) = Self::get_game_other_player(player, state, true)? else {
                     return Ok(())
                 };
 
-                if self_id == player_id {
-                    Self::send(&mut player.write().unwrap().connection, Command::Error("Cannot start a match with yourself".to_string()))?;
+                let language = game.read().unwrap().options.language.clone();
+                if !Self::check_word(player, config, &language, &guess)? {
                     return Ok(())
                 }
 
-                let player_other = {
-                    let state = state.read().unwrap();
-                    let Some(player_other) = state.players.get(&player_id) else {
-                        Self::send(&mut player.write().unwrap().connection, Command::Error("Player id not found".to_string()))?;
-                        return Ok(())
-                    };
-                    player_other.clone()
+                let self_id = player.read().unwrap().id;
+                let (blank_guess, guessers) = {
+                    let mut game = game.write().unwrap();
+                    game.set_word(self_id, guess)?;
+                    (game.mask(), game.guessers())
                 };
 
-                // deadlock if multiple players are trying to match with the same player
-                let mut player_self = player.write().unwrap();
-                let mut player_other = player_other.write().unwrap();
+                let other_id = other_player.read().unwrap().id;
 
-                if player_self.in_game.is_some() || player_other.in_game.is_some() || !player_other.authenticated {
-                    Self::send(&mut player_self.connection, Command::Error("Invalid player id".to_string()))?;
-                    return Ok(())
-                }
-
-                let game = state.write().unwrap().create_game(self_id, player_id);
-                let game_id = game.read().unwrap().id;
-
-                player_self.in_game = Some(game_id);
-                player_other.in_game = Some(game_id);
-
-                Self::send(&mut player_self.connection, Command::RequestMatchGuess)?;
-                Self::send(&mut player_other.connection, Command::PlayerJoined(self_id.to_string()))?;
-            },
-            Command::SetGuess(guess) => {
-                let Some((game, other_player)) = Self::get_game_other_player(&player, &state, true)? else {
-                    return Ok(())
-                };
-
-                if game.read().unwrap().word.is_some() {
-                    Self::send(&mut player.write().unwrap().connection, Command::Error("Word already set".to_string()))?;
-                    return Ok(())
+                // the whole guessing team gets the word to guess
+                let state = state.read().unwrap();
+                Self::send_names(&state, self_id, [other_id])?;
+                Self::send(&mut player.write().unwrap().connection, Command::PlayerJoined(other_id.to_string()))?;
+                for guesser in guessers {
+                    Self::notify(&state, guesser, Command::SetGuess(blank_guess.clone()));
                 }
-
-                let blank_guess = "_".repeat(guess.len());
-                game.write().unwrap().word = Some(guess.clone());
-
-                // deadlock if multiple players are trying to match with the same player
-                let mut player_self = player.write().unwrap();
-                let mut player_other = other_player.write().unwrap();
-
-                Self::send(&mut player_self.connection, Command::PlayerJoined(player_other.id.to_string()))?;
-                Self::send(&mut player_other.connection, Command::SetGuess(blank_guess))?;
             },
             Command::Hint(hint) => {
-                let Some((game, other_player)) = Self::get_game_other_player(&player, &state, true)? else {
+                let Some(game) = Self::get_game(player, state)? else {
                     return Ok(())
                 };
 
-                {
+                let self_id = player.read().unwrap().id;
+                let timed_out = {
+                    // the state is locked before the game, like the timers do
+                    let state = state.read().unwrap();
                     let mut game = game.write().unwrap();
-                    if game.word.is_none() {
-                        Self::send(&mut player.write().unwrap().connection, Command::Error("Not in a game".to_string()))?;
-                        return Ok(())
+                    let timed_out = Self::end_if_timed_out(&state, &mut game)?;
+                    if !timed_out {
+                        game.add_hint(self_id, hint.clone())?;
                     }
-                    game.hints.push(hint.clone());
+                    timed_out.then_some(game.id)
+                };
+
+                if let Some(game_id) = timed_out {
+                    return Self::finish_round(state, game_id)
                 }
 
-                let mut other_player = other_player.write().unwrap();
-                Self::send(&mut other_player.connection, Command::Hint(hint))?;
+                // teammates of the hinter see the hint as well
+                let audience = game.read().unwrap().audience_of(self_id);
+                let state = state.read().unwrap();
+                for player_id in audience {
+                    Self::notify(&state, player_id, Command::Hint(hint.clone()));
+                }
             },
+            Command::CreateTeams(options) => Self::create_teams(player, state, config, options)?,
+            Command::JoinTeam(id, team) => Self::join_team(player, state, id, team)?,
+            Command::LeaveTeams => Self::leave_teams(player, state)?,
+            Command::StartTeams => Self::start_teams(player, state, config)?,
+            Command::TeamChat(message) => Self::team_chat(player, state, message)?,
+            Command::TournamentsRequest => Self::list_tournaments(player, state)?,
+            Command::JoinTournament(id) => Self::join_tournament(player, state, id)?,
+            Command::LeaveTournament(id) => Self::leave_tournament(player, state, id)?,
             Command::Guess(guess) => {
-                let Some((game, other_player)) = Self::get_game_other_player(&player, &state, false)? else {
+                let Some(game) = Self::get_game(player, state)? else {
                     return Ok(())
                 };
 
-                {
-                    let mut game = game.write().unwrap();
-                    let Some(word) = game.word.clone() else {
-                        Self::send(&mut player.write().unwrap().connection, Command::Error("Not in a game".to_string()))?;
-                        return Ok(())
-                    };
-                    game.guesses.push(guess.clone());
-
-                    if word == guess {
-                        game.finished = true;     
-                        game.timestamp = get_timestamp();
-                        game.winner = Some(player.read().unwrap().id);
-                        { 
-                            let mut player = player.write().unwrap();
-                            player.in_game = None;
-                            Self::send(&mut player.connection, Command::Win)?; 
-                        }
-                        Self::broadcast_games(&state.read().unwrap(), &game)?;
-                        let mut other_player = other_player.write().unwrap();
-                        other_player.in_game = None;
-                        Self::send(&mut other_player.connection, Command::Win)?;
-                        return Ok(())
-                    } 
+                let game_id = game.read().unwrap().id;
+                if Self::handle_guess(player, state, &game, guess)? {
+                    Self::finish_round(state, game_id)?;
+                }
+            },
+            Command::SetLanguage(language) => Self::set_language(player, language)?,
+            Command::SetName(name) => Self::set_name(player, state, name)?,
+            Command::Solo(options) => Self::start_solo(player, state, config, options)?,
+            Command::HintRequest => Self::request_hint(player, state)?,
+            Command::Rematch(swap) => Self::rematch(player, state, config, swap)?,
+            Command::Surrender => {
+                if player.read().unwrap().in_game.is_none() {
+                    Self::send(&mut player.write().unwrap().connection, Command::Error("Not in a game".to_string()))?;
+                    return Ok(())
                 }
 
-                let mut other_player = other_player.write().unwrap();
-                Self::send(&mut other_player.connection, Command::Guess(guess))?;
-            }
+                Self::forfeit(player, state, Command::Surrender, EndReason::Surrendered)?;
+            },
 
             _ => println!("Received unhandled command: {:?}", command),
         };
@@ -306,7 +434,53 @@ impl Server {
         Ok(())
     }
 
-    fn get_game_other_player(player: &AMPlayer, state: &ARWServerState, guesser: bool) -> AppResult<Option<(Arc<RwLock<Game>>, Arc<RwLock<Player>>)>> {
+    /// Record a guess and notify both players, returns whether it ended the game.
+    /// The guessing team shares the response to every guess, and guesses in rooms and team games are attributed to the player who made them.
+    fn handle_guess(player: &AMPlayer, state: &ARWServerState, game: &AMGame, guess: String) -> AppResult<bool> {
+        let self_id = player.read().unwrap().id;
+        let state = state.read().unwrap();
+        let mut game = game.write().unwrap();
+        if Self::end_if_timed_out(&state, &mut game)? {
+            return Ok(true)
+        }
+
+        let outcome = game.add_guess(self_id, guess.clone())?;
+        let forward = match game.room_id.is_none() && game.teams.is_empty() {
+            true => Command::Guess(guess),
+            false => Command::PlayerGuess(self_id.to_string(), guess),
+        };
+
+        let guessers = game.guessers();
+        if let Some(response) = &outcome.response {
+            for guesser in &guessers {
+                Self::notify(&state, *guesser, response.clone());
+            }
+        }
+        for player_id in game.audience_of(self_id) {
+            let notice = match &outcome.response {
+                Some(response) if !game.rules().forwards_guesses() && !guessers.contains(&player_id) => response.clone(),
+                _ => forward.clone(),
+            };
+            Self::notify(&state, player_id, notice);
+        }
+
+        if let Some((winner, reason)) = game.rules().decide_end(&game, &outcome) {
+            Self::end_game(&state, &mut game, winner, reason)?;
+            return Ok(true)
+        }
+
+        // everyone sees how many wrong guesses are left, only in matches with a limit
+        if let Some(remaining) = game.remaining_guesses() {
+            for player_id in game.notified_players() {
+                Self::notify(&state, player_id, Command::RemainingGuesses(remaining));
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The game the player is in, they are sent an error if there is none.
+    pub(super) fn get_game(player: &AMPlayer, state: &ARWServerState) -> AppResult<Option<AMGame>> {
         let in_game = player.read().unwrap().in_game;
 
         let Some(game_id) = in_game else {
@@ -314,24 +488,33 @@ impl Server {
             return Ok(None)
         };
 
-        let state = state.read().unwrap();
-        let Some(game) = state.games.get(&game_id) else {
+        let Some(game) = state.read().unwrap().games.get(&game_id).cloned() else {
             let err = Command::Error("Game not found".to_string());
             Self::send(&mut player.write().unwrap().connection, err)?;
             return Ok(None)
         };
 
+        Ok(Some(game))
+    }
+
+    fn get_game_other_player(player: &AMPlayer, state: &ARWServerState, guesser: bool) -> AppResult<Option<(AMGame, AMPlayer)>> {
+        let Some(game) = Self::get_game(player, state)? else {
+            return Ok(None)
+        };
+
         let player_id = if guesser {
             game.read().unwrap().guesser
         } else {
             game.read().unwrap().hinter
         };
+
+        let state = state.read().unwrap();
         let Some(player) = state.players.get(&player_id) else {
             let err = Command::Error("Player in game not found".to_string());
             Self::send(&mut player.write().unwrap().connection, err)?;
             return Ok(None)
         };
 
-        Ok(Some((game.clone(), player.clone())))
+        Ok(Some((game, player.clone())))
     }
 }
@@ -0,0 +1,187 @@
+use std::{collections::{HashMap, HashSet}, fs, sync::Arc};
+
+use crate::{AppError, AppResult, Command};
+
+use super::{normalize, parse_words, AMPlayer, Equivalence, MatchOptions, Server, ServerConfig};
+
+/// Word lists per language which the words set by hinters have to be in.
+/// Languages without a list accept any word made of letters, see [`is_plain_word`].
+pub struct Dictionaries {
+    /// Language and path of every file, read again on [`Dictionaries::reload`].
+    sources: Vec<(String, String)>,
+    equivalence: Equivalence,
+    /// The folded words per language.
+    words: HashMap<String, HashSet<String>>,
+}
+
+impl Dictionaries {
+    pub fn new(equivalence: Equivalence) -> Self {
+        Self {
+            sources: Vec::new(),
+            equivalence,
+            words: HashMap::new(),
+        }
+    }
+
+    /// Add a file from its `LANG:PATH` form and read it, a path ending in `.dic` is read as a hunspell dictionary.
+    /// A file without any words is refused, it would turn down every word of the language.
+    pub fn add_source(&mut self, source: &str) -> AppResult<()> {
+        let (language, path) = split_source(source)?;
+
+        let words = Self::read(path)?;
+        self.insert(language, words);
+        self.sources.push((language.to_string(), path.to_string()));
+        Ok(())
+    }
+
+    /// Read every file again, the old lists are kept if any of them fails or has no words left.
+    pub fn reload(&mut self) -> AppResult<()> {
+        let mut reloaded = Self::new(self.equivalence);
+        for (language, path) in &self.sources {
+            reloaded.insert(language, Self::read(path)?);
+        }
+
+        self.words = reloaded.words;
+        Ok(())
+    }
+
+    fn read(path: &str) -> AppResult<Vec<String>> {
+        let text = fs::read_to_string(path)?;
+        let words = match path.ends_with(".dic") {
+            true => parse_hunspell(&text),
+            false => parse_words(&text),
+        };
+
+        if words.is_empty() {
+            return Err(AppError::InvalidArgument(format!("{} contains no words", path)))
+        }
+
+        Ok(words)
+    }
+
+    fn insert(&mut self, language: &str, words: Vec<String>) {
+        let folded = words.iter().map(|word| self.equivalence.fold(word));
+        self.words.entry(language.to_string()).or_default().extend(folded);
+    }
+
+    /// Number of words per language, sorted by language.
+    pub fn sizes(&self) -> Vec<(&str, usize)> {
+        let mut sizes = self.words.iter().map(|(language, words)| (language.as_str(), words.len())).collect::<Vec<_>>();
+        sizes.sort();
+        sizes
+    }
+
+    /// Check that the word is in the dictionary of the language, or only made of letters without one, the error explains why it is not.
+    pub fn check(&self, language: &str, word: &str) -> Result<(), String> {
+        let word = normalize(word.trim());
+        let Some(words) = self.words.get(language) else {
+            return match is_plain_word(&word) {
+                true => Ok(()),
+                false => Err(format!("{:?} is not a word, only letters are allowed", word)),
+            }
+        };
+
+        match words.contains(&self.equivalence.fold(&word)) {
+            true => Ok(()),
+            false => Err(format!("{:?} is not in the {} dictionary", word, language)),
+        }
+    }
+}
+
+/// Letters, where a hyphen or an apostrophe may join two parts like in "Mam-Mam" or "aujourd'hui".
+fn is_plain_word(word: &str) -> bool {
+    word.split(['-', '\'', '’']).all(|part| !part.is_empty() && part.chars().all(char::is_alphabetic))
+}
+
+/// Split a `LANG:PATH` argument, the language has to be one of [`MatchOptions::LANGUAGES`].
+pub(super) fn split_source(source: &str) -> AppResult<(&str, &str)> {
+    match source.split_once(':') {
+        Some((language, path)) if MatchOptions::LANGUAGES.contains(&language) && !path.is_empty() => Ok((language, path)),
+        _ => Err(AppError::InvalidArgument(source.to_string())),
+    }
+}
+
+/// Split a `[LANG:]PATH` argument, a bare path belongs to the default language of [`MatchOptions`].
+pub(super) fn split_source_or_default(source: &str) -> AppResult<(String, &str)> {
+    match split_source(source) {
+        Ok((language, path)) => Ok((language.to_string(), path)),
+        Err(_) if !source.is_empty() => Ok((MatchOptions::default().language, source)),
+        Err(err) => Err(err),
+    }
+}
+
+/// A hunspell `.dic` file, the first line usually holds the number of words and every word can be followed by `/` and its affix flags.
+/// Affixes are not applied, only the stems count as words.
+fn parse_hunspell(text: &str) -> Vec<String> {
+    let mut lines = text.lines().peekable();
+    // the count is optional, without it the first line is already a word
+    if lines.peek().is_some_and(|line| line.trim().parse::<usize>().is_ok()) {
+        lines.next();
+    }
+
+    lines
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|entry| entry.split('/').next().unwrap_or(entry))
+        .filter(|word| !word.is_empty())
+        .map(normalize)
+        .collect()
+}
+
+impl Server {
+    /// Check a word a hinter wants to set against the dictionary of the language.
+    /// A refused word is answered with [`Command::Error`] followed by [`Command::RequestMatchGuess`], so the hinter can pick another one.
+    pub(super) fn check_word(player: &AMPlayer, config: &Arc<ServerConfig>, language: &str, word: &str) -> AppResult<bool> {
+        let Err(err) = config.dictionaries.read().unwrap().check(language, word) else {
+            return Ok(true)
+        };
+
+        let mut player = player.write().unwrap();
+        Self::send(&mut player.connection, Command::Error(err))?;
+        Self::send(&mut player.connection, Command::RequestMatchGuess)?;
+        Ok(false)
+    }
+
+    /// Read all dictionaries again from the server console.
+    pub(super) fn reload_dictionaries(config: &Arc<ServerConfig>) {
+        let mut dictionaries = config.dictionaries.write().unwrap();
+        match dictionaries.reload() {
+            Ok(()) => {
+                for (language, size) in dictionaries.sizes() {
+                    println!("Loaded {} words for {}", size, language);
+                }
+            },
+            Err(err) => println!("Failed to reload the dictionaries, keeping the old ones: {:?}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut dictionaries = Dictionaries::new(Equivalence::default());
+        dictionaries.insert("lb", parse_words("# comment\nHaus\n\nBre\u{0301}ck\n"));
+        dictionaries.insert("de", parse_hunspell("3\nHaus/S\nBrücke/N\tpo:noun\nKatze\n"));
+
+        assert_eq!(dictionaries.check("lb", "haus"), Ok(()));
+        assert_eq!(dictionaries.check("lb", " Bréck "), Ok(()));
+        assert_eq!(dictionaries.check("lb", "Haus Bréck"), Err("\"Haus Bréck\" is not in the lb dictionary".to_string()));
+        assert!(dictionaries.check("lb", "1234").is_err());
+        assert_eq!(dictionaries.check("de", "Brücke"), Ok(()));
+        assert_eq!(dictionaries.check("de", "katze"), Ok(()));
+        assert!(dictionaries.check("de", "3").is_err());
+        assert_eq!(parse_hunspell("Haus/S\n12\n"), ["Haus", "12"]);
+        assert_eq!(dictionaries.check("fr", "maison"), Ok(()));
+        assert_eq!(dictionaries.check("fr", " aujourd'hui "), Ok(()));
+        assert_eq!(dictionaries.check("fr", "1234"), Err("\"1234\" is not a word, only letters are allowed".to_string()));
+        assert!(dictionaries.check("fr", "une maison").is_err());
+        assert!(dictionaries.check("fr", "-maison").is_err());
+        assert_eq!(dictionaries.sizes(), [("de", 3), ("lb", 2)]);
+
+        assert_eq!(split_source_or_default("fr:mots.txt").unwrap(), ("fr".to_string(), "mots.txt"));
+        assert_eq!(split_source_or_default("words.txt").unwrap(), ("lb".to_string(), "words.txt"));
+        assert!(split_source_or_default("").is_err());
+    }
+}
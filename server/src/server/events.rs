@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{get_timestamp, EndReason, Equivalence, Game, GamePhase, GuessOutcome, MatchOptions, Team};
+
+/// One change of a game at the time it happened, a game is rebuilt by applying its events in order, see [`Game::replay`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    pub game: u32,
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub change: GameChange,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameChange {
+    /// The game with everything set up before the first move, always the first event of a game.
+    Created {
+        hinter: u32,
+        guesser: u32,
+        match_id: Option<u32>,
+        round: u16,
+        room_id: Option<u32>,
+        teams: Vec<Team>,
+        /// Missing in logs written before players had names.
+        #[serde(default)]
+        names: BTreeMap<u32, String>,
+        /// Missing in logs written before players had accounts.
+        #[serde(default)]
+        usernames: BTreeMap<u32, String>,
+        previous_game: Option<u32>,
+        options: MatchOptions,
+        equivalence: Equivalence,
+    },
+    /// The word in NFC form, set by `player`.
+    WordSet {
+        player: u32,
+        word: String,
+    },
+    Hint {
+        player: u32,
+        hint: String,
+    },
+    /// The guess in NFC form, made by `player`.
+    Guess {
+        player: u32,
+        guess: String,
+    },
+    /// The end of the game, a win, a surrender, a disconnect or any other [`EndReason`].
+    Finished {
+        winner: Option<u32>,
+        reason: EndReason,
+    },
+    /// The finished game was removed from memory, written to the archive if there is one, and is not restored anymore.
+    Pruned {
+        archived: bool,
+    },
+}
+
+impl Game {
+    /// Rebuild a game from its events, `None` unless the first event creates it.
+    pub fn replay(events: &[GameEvent]) -> Option<Self> {
+        let (first, rest) = events.split_first()?;
+        let GameChange::Created { hinter, guesser, match_id, round, room_id, teams, names, usernames, previous_game, options, equivalence } = &first.change else {
+            return None
+        };
+
+        let mut game = Game::new(first.game, *hinter, *guesser, options.clone(), *equivalence);
+        game.match_id = *match_id;
+        game.round = *round;
+        game.room_id = *room_id;
+        game.teams = teams.clone();
+        game.names = names.clone();
+        game.usernames = usernames.clone();
+        game.previous_game = *previous_game;
+        game.created = first.timestamp;
+        game.turn_started = first.timestamp;
+
+        for event in rest {
+            game.apply(event);
+        }
+
+        game.events = events.to_vec();
+        Some(game)
+    }
+
+    /// Record the setup of the game as its first event, once teams, rounds and rooms are set.
+    pub(super) fn record_creation(&mut self) {
+        let created = self.creation();
+        self.push_event(created);
+    }
+
+    /// Apply a change which was checked against the rules and record it.
+    pub(super) fn record(&mut self, change: GameChange) -> Option<GuessOutcome> {
+        let event = GameEvent {
+            game: self.id,
+            timestamp: get_timestamp(),
+            change,
+        };
+        let outcome = self.apply(&event);
+        self.push_event(event);
+        outcome
+    }
+
+    fn creation(&self) -> GameEvent {
+        GameEvent {
+            game: self.id,
+            timestamp: self.created,
+            change: GameChange::Created {
+                hinter: self.hinter,
+                guesser: self.guesser,
+                match_id: self.match_id,
+                round: self.round,
+                room_id: self.room_id,
+                teams: self.teams.clone(),
+                names: self.names.clone(),
+                usernames: self.usernames.clone(),
+                previous_game: self.previous_game,
+                options: self.options.clone(),
+                equivalence: self.equivalence,
+            },
+        }
+    }
+
+    fn push_event(&mut self, event: GameEvent) {
+        if let Some(log) = &self.log {
+            if let Err(err) = log.append(&event) {
+                eprintln!("Failed to log event of game {}: {:?}", self.id, err);
+            }
+        }
+
+        self.events.push(event);
+    }
+
+    /// Change the game as the event says without checking it, only a guess has an outcome.
+    fn apply(&mut self, event: &GameEvent) -> Option<GuessOutcome> {
+        match &event.change {
+            GameChange::Created { .. } | GameChange::Pruned { .. } => {},
+            GameChange::WordSet { word, .. } => {
+                self.word = Some(word.clone());
+                self.phase = GamePhase::InProgress;
+                self.started = event.timestamp;
+                self.turn_started = event.timestamp;
+                self.rules().start(self);
+            },
+            GameChange::Hint { hint, .. } => self.hints.push(hint.clone()),
+            GameChange::Guess { guess, .. } => {
+                self.guesses.push(guess.clone());
+                self.turn_started = event.timestamp;
+
+                let outcome = self.rules().evaluate_guess(self, guess);
+                if outcome.wrong {
+                    self.wrong_guesses = self.wrong_guesses.saturating_add(1);
+                }
+                return Some(outcome)
+            },
+            GameChange::Finished { winner, reason } => {
+                self.phase = GamePhase::Finished;
+                self.winner = *winner;
+                self.end_reason = Some(*reason);
+                self.score = self.rules().score(self);
+                self.timestamp = event.timestamp;
+
+                let score = self.score;
+                if let Some(team) = self.teams.iter_mut().find(|team| winner.is_some_and(|winner| team.members.contains(&winner))) {
+                    team.score += score;
+                }
+            },
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let options = MatchOptions {
+            ruleset: "reveal".to_string(),
+            ..Default::default()
+        };
+
+        let mut game = Game::new(4, 10, 20, options, Equivalence::default());
+        game.teams = vec![Team::new(vec![10, 11], 3), Team::new(vec![20, 21], 0)];
+        game.names = BTreeMap::from([(10, "Léa".to_string()), (21, "Jang".to_string())]);
+        game.usernames = BTreeMap::from([(10, "lea".to_string())]);
+        game.record_creation();
+        game.set_word(11, "Mam-Mam".to_string()).unwrap();
+        game.add_hint(10, "who cooks".to_string()).unwrap();
+        game.add_guess(21, "m".to_string()).unwrap();
+        game.add_guess(20, "x".to_string()).unwrap();
+        game.finish(Some(10), EndReason::Surrendered).unwrap();
+
+        let kinds = game.events.iter().map(|event| serde_json::to_value(event).unwrap()["event"].clone()).collect::<Vec<_>>();
+        assert_eq!(kinds, ["created", "word_set", "hint", "guess", "guess", "finished"]);
+
+        let replayed = Game::replay(&game.events).unwrap();
+        assert_eq!(serde_json::to_value(&replayed).unwrap(), serde_json::to_value(&game).unwrap());
+        assert_eq!(replayed.mask(), game.mask());
+        assert_eq!(replayed.teams[0].score, game.teams[0].score);
+
+        assert!(Game::replay(&game.events[1..]).is_none());
+    }
+}
@@ -0,0 +1,481 @@
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use super::{get_timestamp, graphemes, normalize, ruleset, Equivalence, Freeform, GameChange, GameEvent, GameRules, GuessOutcome, History, LetterFeedback, MatchOptions, Team};
+
+pub type GameResult<T> = std::result::Result<T, GameError>;
+
+/// The lifecycle of a game, transitions only ever move forward.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    AwaitingWord,
+    InProgress,
+    Finished,
+}
+
+/// Why a game reached [`GamePhase::Finished`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    Guessed,
+    Surrendered,
+    Disconnected,
+    TimedOut,
+    OutOfGuesses,
+    AbortedByAdmin,
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            EndReason::Guessed => "word guessed",
+            EndReason::Surrendered => "surrendered",
+            EndReason::Disconnected => "player left",
+            EndReason::TimedOut => "time is up",
+            EndReason::OutOfGuesses => "out of guesses",
+            EndReason::AbortedByAdmin => "aborted by admin",
+        };
+
+        write!(f, "{}", reason)
+    }
+}
+
+/// A command that is not allowed in the current phase of the game, or from the sending player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameError {
+    WordAlreadySet,
+    WordNotSet,
+    AlreadyFinished,
+    NotHinter,
+    NotGuesser,
+    /// The word or hint was refused by the rules of the game.
+    Rejected(&'static str),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            GameError::WordAlreadySet => "Word already set",
+            GameError::WordNotSet => "Word has not been set yet",
+            GameError::AlreadyFinished => "Game already finished",
+            GameError::NotHinter => "Only the hinter can do that",
+            GameError::NotGuesser => "Only the guesser can do that",
+            GameError::Rejected(reason) => reason,
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Game {
+    pub(super) id: u32,
+    pub(super) hinter: u32,
+    pub(super) guesser: u32,
+    /// The match the game is a round of.
+    pub(super) match_id: Option<u32>,
+    /// Number of the round within the match, starting at 1.
+    pub(super) round: u16,
+    /// The room the game is played in, one game per guesser of the round.
+    pub(super) room_id: Option<u32>,
+    /// The hinting team followed by the guessing team, empty unless teams play the game.
+    /// The hinter and the guesser are the first members of their team.
+    pub(super) teams: Vec<Team>,
+    /// Display names of the players by id when the game started, players without one are left out.
+    pub(super) names: BTreeMap<u32, String>,
+    /// Usernames of the players who logged in with an account, so they can be followed across sessions, guests are left out.
+    pub(super) usernames: BTreeMap<u32, String>,
+    /// The game played by the same players right before, the previous round or the end of the match this one is a rematch of.
+    pub(super) previous_game: Option<u32>,
+    /// Settings agreed on when the match was created, including the name of the [`GameRules`].
+    pub(super) options: MatchOptions,
+    pub(super) equivalence: Equivalence,
+    pub(super) word: Option<String>,
+    pub(super) guesses: Vec<String>,
+    pub(super) hints: Vec<String>,
+    /// Which graphemes of the word the guesser can see.
+    #[serde(skip)]
+    pub(super) revealed: Vec<bool>,
+    /// The mask after every guess, only filled by the reveal ruleset.
+    pub(super) reveals: Vec<String>,
+    /// Feedback for each guess, `None` for guesses of a different length, only filled by the wordle ruleset.
+    pub(super) feedback: Vec<Option<Vec<LetterFeedback>>>,
+    /// Guesses which brought the guesser no closer to the word.
+    pub(super) wrong_guesses: u16,
+    pub(super) winner: Option<u32>,
+    /// Points awarded to the winner by the ruleset.
+    pub(super) score: u32,
+    pub(super) phase: GamePhase,
+    pub(super) end_reason: Option<EndReason>,
+    /// When the word was set and guessing started, 0 before that.
+    pub(super) started: u64,
+    /// When the player whose turn it is could first act.
+    #[serde(skip)]
+    pub(super) turn_started: u64,
+    pub(super) timestamp: u64,
+    /// When the game was created, before the word was set.
+    #[serde(skip)]
+    pub(super) created: u64,
+    /// Every change of the game so far, see [`Game::replay`].
+    #[serde(skip)]
+    pub(super) events: Vec<GameEvent>,
+    /// Where the events are written as they happen, `None` if they are only kept in memory.
+    #[serde(skip)]
+    pub(super) log: Option<Arc<History>>,
+}
+
+impl Game {
+    pub(super) fn new(id: u32, hinter: u32, guesser: u32, options: MatchOptions, equivalence: Equivalence) -> Self {
+        Self {
+            id,
+            hinter,
+            guesser,
+            match_id: None,
+            round: 1,
+            room_id: None,
+            teams: Vec::new(),
+            names: BTreeMap::new(),
+            usernames: BTreeMap::new(),
+            previous_game: None,
+            options,
+            equivalence,
+            word: None,
+            guesses: Vec::new(),
+            hints: Vec::new(),
+            revealed: Vec::new(),
+            reveals: Vec::new(),
+            feedback: Vec::new(),
+            wrong_guesses: 0,
+            winner: None,
+            score: 0,
+            phase: GamePhase::AwaitingWord,
+            end_reason: None,
+            started: 0,
+            turn_started: get_timestamp(),
+            timestamp: 0,
+            created: get_timestamp(),
+            events: Vec::new(),
+            log: None,
+        }
+    }
+
+    /// The rules the game is played with, falls back to freeform for unknown names.
+    pub fn rules(&self) -> &'static dyn GameRules {
+        ruleset(&self.options.ruleset).unwrap_or(&Freeform)
+    }
+
+    /// Check a word against the rules and the allowed length of a match, returns it trimmed in NFC form.
+    pub(super) fn check_word(options: &MatchOptions, word: &str) -> GameResult<String> {
+        let word = normalize(word.trim());
+        ruleset(&options.ruleset).unwrap_or(&Freeform).validate_word(&word)?;
+        if !options.allows_length(graphemes(&word).len()) {
+            return Err(GameError::Rejected("Word length is not allowed in this match"))
+        }
+
+        Ok(word)
+    }
+
+    /// The secret word, empty until it is set.
+    pub fn word(&self) -> &str {
+        self.word.as_deref().unwrap_or_default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == GamePhase::Finished
+    }
+
+    /// The player the game is waiting for, the hinter until the word is set and the guesser after that.
+    pub fn turn_of(&self) -> Option<u32> {
+        match self.phase {
+            GamePhase::AwaitingWord => Some(self.hinter),
+            GamePhase::InProgress => Some(self.guesser),
+            GamePhase::Finished => None,
+        }
+    }
+
+    /// When the current turn runs out, `None` without a turn limit.
+    fn turn_deadline(&self) -> Option<u64> {
+        let limit = self.options.turn_limit.filter(|_| !self.is_finished())?;
+        Some(self.turn_started + limit as u64)
+    }
+
+    /// When the time limit of the match runs out, it starts once the word is set.
+    fn match_deadline(&self) -> Option<u64> {
+        let limit = self.options.time_limit.filter(|_| self.phase == GamePhase::InProgress)?;
+        Some(self.started + limit as u64)
+    }
+
+    /// Seconds until the next deadline, `None` if the game has none.
+    pub fn time_left(&self, now: u64) -> Option<u64> {
+        [self.turn_deadline(), self.match_deadline()].into_iter().flatten().min()
+            .map(|deadline| deadline.saturating_sub(now))
+    }
+
+    /// Returns the winner if a deadline has passed at `now`.
+    /// The hinter wins if the word was not found in time, otherwise the opponent of the player who let their turn run out.
+    pub fn timeout_winner(&self, now: u64) -> Option<u32> {
+        if self.match_deadline().is_some_and(|deadline| now >= deadline) {
+            return Some(self.hinter)
+        }
+
+        if self.turn_deadline().is_some_and(|deadline| now >= deadline) {
+            return self.turn_of().map(|player_id| self.opponent_of(player_id))
+        }
+
+        None
+    }
+
+    /// Whether the player sets the word and gives hints, any member of the hinting team does.
+    pub fn is_hinter(&self, player_id: u32) -> bool {
+        match self.teams.first() {
+            Some(team) => team.members.contains(&player_id),
+            None => self.hinter == player_id,
+        }
+    }
+
+    /// Whether the player may guess, any member of the guessing team may.
+    pub fn is_guesser(&self, player_id: u32) -> bool {
+        match self.teams.get(1) {
+            Some(team) => team.members.contains(&player_id),
+            None => self.guesser == player_id,
+        }
+    }
+
+    /// Everyone who is sent the word to guess and the response to every guess.
+    pub fn guessers(&self) -> Vec<u32> {
+        match self.teams.get(1) {
+            Some(team) => team.members.clone(),
+            None => vec![self.guesser],
+        }
+    }
+
+    /// The players who are sent the progress of the game, a room hinter follows the games of a round through the room instead.
+    pub fn notified_players(&self) -> Vec<u32> {
+        if self.room_id.is_some() {
+            return vec![self.guesser]
+        }
+
+        match self.teams.as_slice() {
+            [] => vec![self.hinter, self.guesser],
+            teams => teams.iter().flat_map(|team| team.members.iter().copied()).collect(),
+        }
+    }
+
+    /// Everyone who sees the hints and guesses of the player, their opponents and their teammates.
+    pub fn audience_of(&self, player_id: u32) -> Vec<u32> {
+        let mut players = self.notified_players();
+        if self.room_id.is_some() {
+            players.push(self.hinter);
+        }
+
+        players.retain(|id| *id != player_id);
+        players
+    }
+
+    /// Returns the id of the other player in the game, in team games the first member of the other team.
+    pub fn opponent_of(&self, player_id: u32) -> u32 {
+        if self.is_hinter(player_id) {
+            self.guesser
+        } else {
+            self.hinter
+        }
+    }
+
+    /// Set the secret word in NFC form, moves the game from [`GamePhase::AwaitingWord`] to [`GamePhase::InProgress`].
+    pub fn set_word(&mut self, player_id: u32, word: String) -> GameResult<()> {
+        if !self.is_hinter(player_id) {
+            return Err(GameError::NotHinter)
+        }
+
+        match self.phase {
+            GamePhase::AwaitingWord => {
+                let word = Self::check_word(&self.options, &word)?;
+                self.record(GameChange::WordSet { player: player_id, word });
+                Ok(())
+            },
+            GamePhase::InProgress => Err(GameError::WordAlreadySet),
+            GamePhase::Finished => Err(GameError::AlreadyFinished),
+        }
+    }
+
+    pub fn add_hint(&mut self, player_id: u32, hint: String) -> GameResult<()> {
+        if !self.is_hinter(player_id) {
+            return Err(GameError::NotHinter)
+        }
+        self.expect_in_progress()?;
+        if self.options.hint_limit.is_some_and(|limit| self.hints.len() >= limit as usize) {
+            return Err(GameError::Rejected("No hints left in this match"))
+        }
+        self.rules().validate_hint(self, &hint)?;
+
+        self.record(GameChange::Hint { player: player_id, hint });
+        Ok(())
+    }
+
+    /// Record a guess and let the rules evaluate it.
+    pub fn add_guess(&mut self, player_id: u32, guess: String) -> GameResult<GuessOutcome> {
+        if !self.is_guesser(player_id) {
+            return Err(GameError::NotGuesser)
+        }
+        self.expect_in_progress()?;
+
+        let guess = normalize(&guess);
+        Ok(self.record(GameChange::Guess { player: player_id, guess }).expect("a guess has an outcome"))
+    }
+
+    /// Wrong guesses the guesser can still make, `None` if the match has no limit.
+    pub fn remaining_guesses(&self) -> Option<u16> {
+        self.options.max_wrong_guesses.map(|max| max.saturating_sub(self.wrong_guesses))
+    }
+
+    /// Whether the guesser has used up the wrong guesses allowed in the match.
+    pub fn is_out_of_guesses(&self) -> bool {
+        self.remaining_guesses() == Some(0)
+    }
+
+    /// Returns the word with every grapheme the guesser has not uncovered replaced by `_`.
+    pub fn mask(&self) -> String {
+        graphemes(self.word()).into_iter().zip(&self.revealed)
+            .map(|(g, revealed)| if *revealed { g } else { "_" })
+            .collect()
+    }
+
+    /// End the game, can be done from any phase except [`GamePhase::Finished`].
+    pub fn finish(&mut self, winner: Option<u32>, reason: EndReason) -> GameResult<()> {
+        if self.is_finished() {
+            return Err(GameError::AlreadyFinished)
+        }
+
+        self.record(GameChange::Finished { winner, reason });
+        Ok(())
+    }
+
+    fn expect_in_progress(&self) -> GameResult<()> {
+        match self.phase {
+            GamePhase::AwaitingWord => Err(GameError::WordNotSet),
+            GamePhase::InProgress => Ok(()),
+            GamePhase::Finished => Err(GameError::AlreadyFinished),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(ruleset: &str) -> MatchOptions {
+        MatchOptions {
+            ruleset: ruleset.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let mut game = Game::new(1, 10, 20, options("freeform"), Equivalence::default());
+        assert_eq!(game.phase, GamePhase::AwaitingWord);
+
+        assert_eq!(game.add_guess(20, "word".to_string()).err(), Some(GameError::WordNotSet));
+        game.set_word(10, " word ".to_string()).unwrap();
+        assert_eq!(game.phase, GamePhase::InProgress);
+        assert_eq!(game.word(), "word");
+
+        assert!(!game.add_guess(20, "nope".to_string()).unwrap().correct);
+        assert!(game.add_guess(20, "word".to_string()).unwrap().correct);
+
+        game.finish(Some(20), EndReason::Guessed).unwrap();
+        assert!(game.is_finished());
+        assert_eq!(game.end_reason, Some(EndReason::Guessed));
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        let mut game = Game::new(1, 10, 20, options("freeform"), Equivalence::default());
+
+        assert_eq!(game.set_word(20, "word".to_string()), Err(GameError::NotHinter));
+        game.set_word(10, "word".to_string()).unwrap();
+        assert_eq!(game.set_word(10, "other".to_string()), Err(GameError::WordAlreadySet));
+        assert_eq!(game.add_hint(20, "hint".to_string()), Err(GameError::NotHinter));
+
+        game.finish(None, EndReason::AbortedByAdmin).unwrap();
+        assert_eq!(game.finish(Some(10), EndReason::Surrendered), Err(GameError::AlreadyFinished));
+        assert_eq!(game.add_hint(10, "hint".to_string()), Err(GameError::AlreadyFinished));
+    }
+
+    #[test]
+    fn test_reveal() {
+        let mut game = Game::new(1, 10, 20, options("reveal"), Equivalence::default());
+        game.set_word(10, "Mam-Mam".to_string()).unwrap();
+        assert_eq!(game.mask(), "___-___");
+
+        assert!(!game.add_guess(20, "m".to_string()).unwrap().correct);
+        assert_eq!(game.mask(), "M_m-M_m");
+        assert!(!game.add_guess(20, "xa".to_string()).unwrap().correct);
+        assert_eq!(game.mask(), "M_m-M_m");
+        assert!(game.add_guess(20, "A".to_string()).unwrap().correct);
+
+        assert_eq!(game.reveals, ["M_m-M_m", "M_m-M_m", "Mam-Mam"]);
+    }
+
+    #[test]
+    fn test_reveal_graphemes() {
+        let equivalence = Equivalence {
+            ignore_diacritics: true,
+            ..Default::default()
+        };
+
+        let mut game = Game::new(1, 10, 20, options("reveal"), equivalence);
+        game.set_word(10, "Le\u{0308}tzebuerg".to_string()).unwrap();
+        assert_eq!(game.mask(), "__________");
+
+        game.add_guess(20, "e".to_string()).unwrap();
+        assert_eq!(game.mask(), "_ë__e__e__");
+        assert!(game.add_guess(20, "letzebuerg".to_string()).unwrap().correct);
+    }
+
+    #[test]
+    fn test_options() {
+        let mut options = options("reveal");
+        options.apply("guesses=2").unwrap();
+        options.apply("hints=1").unwrap();
+        options.apply("length=3-5").unwrap();
+
+        let mut game = Game::new(1, 10, 20, options, Equivalence::default());
+        assert!(matches!(game.set_word(10, "Lëtzebuerg".to_string()), Err(GameError::Rejected(_))));
+        game.set_word(10, "Bréck".to_string()).unwrap();
+
+        game.add_hint(10, "crosses a river".to_string()).unwrap();
+        assert!(matches!(game.add_hint(10, "made of stone".to_string()), Err(GameError::Rejected(_))));
+
+        assert!(!game.add_guess(20, "b".to_string()).unwrap().wrong);
+        assert!(game.add_guess(20, "x".to_string()).unwrap().wrong);
+        assert_eq!(game.remaining_guesses(), Some(1));
+        assert!(!game.is_out_of_guesses());
+        assert!(game.add_guess(20, "bridge".to_string()).unwrap().wrong);
+        assert!(game.is_out_of_guesses());
+    }
+
+    #[test]
+    fn test_deadlines() {
+        let mut options = options("freeform");
+        options.apply("time=60").unwrap();
+        options.apply("turn=20").unwrap();
+
+        let mut game = Game::new(1, 10, 20, options, Equivalence::default());
+        let now = game.turn_started;
+        assert_eq!(game.time_left(now + 5), Some(15));
+        assert_eq!(game.timeout_winner(now + 20), Some(20));
+
+        game.set_word(10, "word".to_string()).unwrap();
+        game.started = now;
+        game.turn_started = now + 50;
+        assert_eq!(game.time_left(now + 55), Some(5));
+        assert_eq!(game.timeout_winner(now + 59), None);
+        assert_eq!(game.timeout_winner(now + 60), Some(10));
+
+        game.turn_started = now;
+        assert_eq!(game.timeout_winner(now + 20), Some(10));
+    }
+}
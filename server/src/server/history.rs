@@ -0,0 +1,198 @@
+use std::{collections::BTreeMap, fs::{self, File, OpenOptions}, io::{BufRead, BufReader, BufWriter, ErrorKind, Write}, sync::{Arc, RwLock}};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{AppError, AppResult};
+
+use super::{Game, GameChange, GameEvent, ServerState};
+
+/// The events of all games appended to a JSON lines file as they happen, one event per line, and replayed when the server starts.
+/// Archived games and accounts are kept in JSON lines files the same way.
+#[derive(Debug)]
+pub struct History {
+    file: File,
+}
+
+impl History {
+    /// Open the file for appending, creating it if needed.
+    pub fn open(path: &str) -> AppResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file,
+        })
+    }
+
+    /// Read every entry stored so far, a missing file is an empty history.
+    /// Lines which cannot be parsed, like one cut off by a crash, are skipped.
+    pub fn load<T: DeserializeOwned>(path: &str) -> AppResult<Vec<T>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => eprintln!("Skipping line {} of {}: {}", i + 1, path, err),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Replace the file with the given entries, they are written to a temporary file first so a crash keeps the old one.
+    pub fn rewrite<'a, T: Serialize + 'a>(path: &str, entries: impl IntoIterator<Item = &'a T>) -> AppResult<()> {
+        let temp = format!("{}.tmp", path);
+        let mut file = BufWriter::new(File::create(&temp)?);
+        for entry in entries {
+            serde_json::to_writer(&mut file, entry).map_err(AppError::Serde)?;
+            file.write_all(b"\n")?;
+        }
+        file.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Append an entry, written with a single call so the lines of concurrent games do not interleave.
+    pub fn append(&self, entry: &impl Serialize) -> AppResult<()> {
+        let mut line = serde_json::to_string(entry).map_err(AppError::Serde)?;
+        line.push('\n');
+        (&self.file).write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl ServerState {
+    /// Replay the stored events and put the finished games back, then open the history for new events.
+    /// Games which were still running when the server stopped are left out, so are games which were pruned.
+    /// The file is compacted to the events of the restored games, so left out games are not read again on every start.
+    /// The ids of new games, matches and rooms continue after the highest stored ones, so they never repeat.
+    /// Left out games holding one of the highest ids stay in the file for that, otherwise a later start would hand them out again.
+    pub(super) fn restore_history(&mut self, path: &str) -> AppResult<usize> {
+        let mut events = BTreeMap::<u32, Vec<GameEvent>>::new();
+        for event in History::load::<GameEvent>(path)? {
+            events.entry(event.game).or_default().push(event);
+        }
+
+        let mut games = Vec::new();
+        let mut left_out = Vec::new();
+        for (id, events) in events {
+            self.next_game_id = self.next_game_id.max(id + 1);
+            let Some(game) = Game::replay(&events) else {
+                eprintln!("Skipping game {} of {}: its events do not start with its creation", id, path);
+                left_out.push((id, None, None, events));
+                continue
+            };
+            if let Some(match_id) = game.match_id {
+                self.next_match_id = self.next_match_id.max(match_id + 1);
+            }
+            if let Some(room_id) = game.room_id {
+                self.next_room_id = self.next_room_id.max(room_id + 1);
+            }
+            if !game.is_finished() || events.iter().any(|event| matches!(event.change, GameChange::Pruned { .. })) {
+                left_out.push((id, game.match_id, game.room_id, events));
+                continue
+            }
+
+            games.push(game);
+        }
+
+        let highest = |id: Option<u32>, next: u32| id.is_some_and(|id| id + 1 == next);
+        let kept = left_out.into_iter()
+            .filter(|(id, match_id, room_id, _)| {
+                highest(Some(*id), self.next_game_id) || highest(*match_id, self.next_match_id) || highest(*room_id, self.next_room_id)
+            })
+            .flat_map(|(_, _, _, events)| events)
+            .collect::<Vec<_>>();
+        History::rewrite(path, games.iter().flat_map(|game| &game.events).chain(&kept))?;
+
+        let history = Arc::new(History::open(path)?);
+        let count = games.len();
+        for mut game in games {
+            game.log = Some(history.clone());
+            self.retention.finished.insert((game.timestamp, game.id));
+            self.games.insert(game.id, Arc::new(RwLock::new(game)));
+        }
+
+        self.history = Some(history);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EndReason, Equivalence, MatchOptions};
+
+    #[test]
+    fn test_restore() {
+        let path = std::env::temp_dir().join(format!("history-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let history = Arc::new(History::open(path).unwrap());
+
+        let mut game = Game::new(7, 1, 2, MatchOptions::default(), Equivalence::default());
+        game.log = Some(history.clone());
+        game.match_id = Some(3);
+        game.record_creation();
+        game.set_word(1, "Kaffi".to_string()).unwrap();
+        game.add_guess(2, "kaffi".to_string()).unwrap();
+        game.finish(Some(2), EndReason::Guessed).unwrap();
+
+        let mut running = Game::new(8, 1, 2, MatchOptions::default(), Equivalence::default());
+        running.log = Some(history.clone());
+        running.record_creation();
+        running.set_word(1, "Téi".to_string()).unwrap();
+        (&history.file).write_all(b"{\"game\": 8, \"cut off").unwrap();
+
+        let mut state = ServerState::new();
+        assert_eq!(state.restore_history(path).unwrap(), 1);
+        // the cut off line is gone from the file, the running game stays as it holds the highest id
+        assert_eq!(History::load::<GameEvent>(path).unwrap(), [game.events.clone(), running.events].concat());
+        std::fs::remove_file(path).unwrap();
+
+        let restored = state.games[&7].read().unwrap();
+        assert_eq!(restored.word(), "Kaffi");
+        assert_eq!(restored.guesses, ["kaffi"]);
+        assert_eq!(restored.end_reason, Some(EndReason::Guessed));
+        assert_eq!(restored.events, game.events);
+        assert_eq!((state.next_game_id, state.next_match_id, state.next_room_id), (9, 4, 1));
+    }
+
+    #[test]
+    fn test_restore_keeps_ids() {
+        let path = std::env::temp_dir().join(format!("history-ids-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let history = Arc::new(History::open(path).unwrap());
+
+        for (id, room_id) in [(4, None), (5, Some(2))] {
+            let mut game = Game::new(id, 1, 2, MatchOptions::default(), Equivalence::default());
+            game.log = Some(history.clone());
+            game.match_id = Some(id - 3);
+            game.room_id = room_id;
+            game.record_creation();
+            game.set_word(1, "Kaffi".to_string()).unwrap();
+            game.finish(Some(1), EndReason::Surrendered).unwrap();
+            game.record(GameChange::Pruned { archived: true });
+        }
+        drop(history);
+
+        // every game was pruned, restarting twice still continues after their ids
+        for _ in 0..2 {
+            let mut state = ServerState::new();
+            assert_eq!(state.restore_history(path).unwrap(), 0);
+            assert_eq!((state.next_game_id, state.next_match_id, state.next_room_id), (6, 3, 3));
+        }
+        // only the game holding the highest ids is left in the file
+        assert!(History::load::<GameEvent>(path).unwrap().iter().all(|event| event.game == 5));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use crate::{AppResult, Command};
+
+use super::{get_timestamp, shares_language, AMPlayer, ARWServerState, MatchOptions, Server, ServerConfig, ServerState};
+
+/// A pending match request, stored in [`ServerState::invites`] under the id of the inviting player.
+pub struct Invite {
+    pub(super) to: u32,
+    pub(super) options: MatchOptions,
+    /// The player who sets the word in the first round, the inviting player unless a rematch swaps the roles.
+    pub(super) hinter: u32,
+    /// The last game of the previous match if this is a rematch.
+    pub(super) previous_game: Option<u32>,
+    timestamp: u64,
+}
+
+impl Invite {
+    fn new(from: u32, to: u32, options: MatchOptions) -> Self {
+        Self {
+            to,
+            options,
+            hinter: from,
+            previous_game: None,
+            timestamp: get_timestamp(),
+        }
+    }
+
+    pub(super) fn rematch(to: u32, options: MatchOptions, hinter: u32, previous_game: u32) -> Self {
+        Self {
+            to,
+            options,
+            hinter,
+            previous_game: Some(previous_game),
+            timestamp: get_timestamp(),
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        now >= self.timestamp + Server::INVITE_TIMEOUT
+    }
+}
+
+impl ServerState {
+    /// Remove every invite sent by or to the player, returns them as `(from, to)` pairs.
+    fn remove_invites_of(&mut self, player_id: u32) -> Vec<(u32, u32)> {
+        let mut removed = Vec::new();
+        self.invites.retain(|from, invite| {
+            let keep = *from != player_id && invite.to != player_id;
+            if !keep {
+                removed.push((*from, invite.to));
+            }
+            keep
+        });
+        removed
+    }
+}
+
+impl Server {
+    /// Send a match invite to the player with the given id, replacing any previous invite.
+    /// The match is played with the given options, an empty ruleset name selects freeform.
+    pub(super) fn request_match(player: &AMPlayer, state: &ARWServerState, id: String, mut options: MatchOptions) -> AppResult<()> {
+        let (self_id, busy) = {
+            let player = player.read().unwrap();
+            (player.id, player.is_busy())
+        };
+
+        let Ok(player_id) = id.parse() else {
+            return Self::send_error(player, "Invalid player id")
+        };
+
+        if self_id == player_id {
+            return Self::send_error(player, "Cannot start a match with yourself")
+        }
+
+        if busy {
+            return Self::send_error(player, "Cannot send an invite while playing")
+        }
+
+        if let Err(err) = options.normalize() {
+            return Self::send_error(player, err)
+        }
+
+        let player_other = {
+            let state = state.read().unwrap();
+            let Some(player_other) = state.players.get(&player_id) else {
+                return Self::send_error(player, "Player id not found")
+            };
+            player_other.clone()
+        };
+
+        {
+            let player_other = player_other.read().unwrap();
+            if player_other.is_busy() || !player_other.authenticated {
+                return Self::send_error(player, "Player is not available")
+            }
+            // like the opponents list and the queue, nobody is invited to a match in a language they do not play
+            if !shares_language(&Some(options.language.clone()), &player_other.language) {
+                return Self::send_error(player, "Player plays in a different language")
+            }
+        }
+
+        let previous = state.write().unwrap().invites.insert(self_id, Invite::new(self_id, player_id, options.clone()));
+
+        let state = state.read().unwrap();
+        if let Some(previous) = previous {
+            if previous.to != player_id {
+                Self::notify(&state, previous.to, Command::CancelMatch(self_id.to_string()));
+            }
+        }
+
+        let result = Self::send_names(&state, player_id, [self_id])
+            .and_then(|()| Self::send_to(&state, player_id, Command::MatchInvite(self_id.to_string(), options)));
+        if let Err(err) = result {
+            eprintln!("Failed to notify player {}: {:?}", player_id, err);
+        }
+
+        Ok(())
+    }
+
+    /// Accept an invite from the player with the given id and start the match, the inviting player is the hinter of the first round unless a rematch swapped the roles.
+    pub(super) fn accept_match(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, id: String) -> AppResult<()> {
+        let self_id = player.read().unwrap().id;
+
+        let Ok(player_id) = id.parse() else {
+            return Self::send_error(player, "Invalid player id")
+        };
+
+        let invite = {
+            let mut state = state.write().unwrap();
+            match state.invites.get(&player_id) {
+                Some(invite) if invite.to == self_id => state.invites.remove(&player_id),
+                _ => None,
+            }
+        };
+
+        let Some(invite) = invite else {
+            return Self::send_error(player, format!("No pending invite from player {}", player_id))
+        };
+
+        if invite.is_expired(get_timestamp()) {
+            return Self::send_error(player, "Invite expired")
+        }
+
+        // accepting withdraws the invite this player has sent to someone else
+        let own_invite = state.write().unwrap().invites.remove(&self_id);
+        if let Some(own_invite) = own_invite {
+            Self::notify(&state.read().unwrap(), own_invite.to, Command::CancelMatch(self_id.to_string()));
+        }
+
+        // the state is locked first, so nobody can hold one of the players while waiting for it
+        let mut state = state.write().unwrap();
+        let Some(player_other) = state.players.get(&player_id).cloned() else {
+            drop(state);
+            return Self::send_error(player, "Player id not found")
+        };
+
+        let busy = player.read().unwrap().is_busy() || player_other.read().unwrap().is_busy();
+        if busy {
+            drop(state);
+            return Self::send_error(player, "Player is already in a game")
+        }
+
+        state.matchmaker.leave(player_id);
+        state.matchmaker.leave(self_id);
+
+        let (hinter, guesser) = if invite.hinter == player_id {
+            (player_id, self_id)
+        } else {
+            (self_id, player_id)
+        };
+        let options = config.apply_defaults(invite.options);
+        let match_id = state.create_match(hinter, guesser, options, config.equivalence, invite.previous_game);
+        Self::start_round(&mut state, match_id)
+    }
+
+    /// Decline an invite from the player with the given id, the inviting player is notified.
+    pub(super) fn decline_match(player: &AMPlayer, state: &ARWServerState, id: String) -> AppResult<()> {
+        let self_id = player.read().unwrap().id;
+
+        let Ok(player_id) = id.parse() else {
+            return Self::send_error(player, "Invalid player id")
+        };
+
+        let invite = {
+            let mut state = state.write().unwrap();
+            match state.invites.get(&player_id) {
+                Some(invite) if invite.to == self_id => state.invites.remove(&player_id),
+                _ => None,
+            }
+        };
+
+        if invite.is_none() {
+            return Self::send_error(player, format!("No pending invite from player {}", player_id))
+        }
+
+        Self::notify(&state.read().unwrap(), player_id, Command::DeclineMatch(self_id.to_string()));
+        Ok(())
+    }
+
+    /// Withdraw the invite sent to the player with the given id, the invited player is notified.
+    pub(super) fn cancel_match(player: &AMPlayer, state: &ARWServerState, id: String) -> AppResult<()> {
+        let self_id = player.read().unwrap().id;
+
+        let Ok(player_id) = id.parse() else {
+            return Self::send_error(player, "Invalid player id")
+        };
+
+        let invite = {
+            let mut state = state.write().unwrap();
+            match state.invites.get(&self_id) {
+                Some(invite) if invite.to == player_id => state.invites.remove(&self_id),
+                _ => None,
+            }
+        };
+
+        if invite.is_none() {
+            return Self::send_error(player, format!("No pending invite to player {}", player_id))
+        }
+
+        Self::notify(&state.read().unwrap(), player_id, Command::CancelMatch(self_id.to_string()));
+        Ok(())
+    }
+
+    /// Drop all invites of a disconnecting player and notify the other side of each one.
+    pub(super) fn clear_invites(player_id: u32, state: &ARWServerState) -> AppResult<()> {
+        let removed = state.write().unwrap().remove_invites_of(player_id);
+
+        let state = state.read().unwrap();
+        for (from, to) in removed {
+            let (other, command) = if from == player_id {
+                (to, Command::CancelMatch(from.to_string()))
+            } else {
+                (from, Command::DeclineMatch(to.to_string()))
+            };
+            Self::notify(&state, other, command);
+        }
+
+        Ok(())
+    }
+
+    /// Remove expired invites and notify both players, called by the timers every second.
+    pub(super) fn sweep_invites(state: &ARWServerState, now: u64) {
+        let mut expired = Vec::new();
+        state.write().unwrap().invites.retain(|from, invite| {
+            let keep = !invite.is_expired(now);
+            if !keep {
+                expired.push((*from, invite.to));
+            }
+            keep
+        });
+
+        let state = state.read().unwrap();
+        for (from, to) in expired {
+            let notified = Self::send_to(&state, from, Command::InviteExpired(to.to_string()))
+                .and_then(|_| Self::send_to(&state, to, Command::InviteExpired(from.to_string())));
+
+            if let Err(err) = notified {
+                eprintln!("Failed to notify expired invite: {:?}", err);
+            }
+        }
+    }
+}
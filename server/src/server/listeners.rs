@@ -1,8 +1,8 @@
-use std::{net::TcpListener, os::unix::net::UnixListener, thread};
+use std::{net::TcpListener, os::unix::net::UnixListener, sync::Arc, thread};
 
 use crate::{AppResult, Connection};
 
-use super::Server;
+use super::{AMPlayer, ARWServerState, Server, ServerConfig};
 
 impl Server {
     /// Start a unix listener on a new thread on [`Self::SOCKET_PATH`].
@@ -29,15 +29,10 @@ impl Server {
                         let state = state.clone();
                         let config = config.clone();
                         thread::spawn(move || {
-                            let id = player.read().unwrap().id;
-
-                            match Self::handle_client(player, state.clone(), config) {
+                            match Self::serve(player, state, config) {
                                 Ok(_) => println!("Client disconnected"),
                                 Err(err) => eprintln!("Closing connection -> Client error: {:?}", err),
                             }
-
-                            let mut state = state.write().unwrap();
-                            state.players.remove(&id);
                         });
                     }
                     Err(err) => {
@@ -79,7 +74,7 @@ impl Server {
                         let state = state.clone();
                         let config = config.clone();
                         thread::spawn(move || {
-                            match Self::handle_client(player, state.clone(), config) {
+                            match Self::serve(player, state, config) {
                                 Ok(_) => println!("Client disconnected: {:?}", peer_addr),
                                 Err(err) => eprintln!("Closing connection -> Client error: {:?}", err),
                             }
@@ -94,4 +89,54 @@ impl Server {
 
         Ok(())
     }
+
+    /// Handle the client until it disconnects, then remove the player so its account and name are free again.
+    fn serve(player: AMPlayer, state: ARWServerState, config: Arc<ServerConfig>) -> AppResult<()> {
+        let id = player.read().unwrap().id;
+        let result = Self::handle_client(player, state.clone(), config);
+
+        state.write().unwrap().players.remove(&id);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, sync::RwLock};
+
+    use super::*;
+    use crate::{server::ServerState, Command};
+
+    #[test]
+    fn test_login_after_disconnect() {
+        let state = Arc::new(RwLock::new(ServerState::new()));
+        let config = Arc::new(ServerConfig::from_args(std::iter::empty()).unwrap());
+
+        // register, disconnect and log in with the same account on a new connection
+        let commands = [Command::Register, Command::Login];
+        for command in commands {
+            let (stream, client) = UnixStream::pair().unwrap();
+            let player = state.write().unwrap().create_player(Connection::unix(stream).unwrap());
+            let serving = {
+                let state = state.clone();
+                let config = config.clone();
+                thread::spawn(move || Server::serve(player, state, config))
+            };
+
+            let mut client = Connection::unix(client).unwrap();
+            let reader = client.reader.clone();
+            {
+                let mut reader = reader.lock().unwrap();
+                assert!(matches!(Server::receive(reader.reader()).unwrap(), Command::PasswordRequest));
+                Server::send(&mut client, command("Léa_2".to_string(), "password".to_string())).unwrap();
+                assert!(matches!(Server::receive(reader.reader()).unwrap(), Command::SetId(_)));
+            }
+
+            drop(reader);
+            drop(client);
+            serving.join().unwrap().unwrap();
+        }
+
+        assert!(state.read().unwrap().players.is_empty());
+    }
 }
@@ -0,0 +1,486 @@
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc};
+
+use serde::Serialize;
+
+use crate::{AppError, AppResult, Command};
+
+use super::{get_timestamp, AMPlayer, ARWServerState, EndReason, Equivalence, Game, Invite, MatchOptions, Server, ServerConfig, ServerState};
+
+/// The final standing of a match.
+#[derive(Serialize, Debug, Clone)]
+pub struct MatchResult {
+    /// `None` for a draw or an aborted match.
+    pub winner: Option<u32>,
+    pub wins: [u16; 2],
+    pub score: [u32; 2],
+    pub timestamp: u64,
+}
+
+/// A best-of series of games between two players, the roles swap every round.
+#[derive(Serialize, Debug)]
+pub struct Match {
+    pub(super) id: u32,
+    /// The players in the order of `wins` and `score`, the first one is the hinter of the first round.
+    pub(super) players: [u32; 2],
+    pub(super) options: MatchOptions,
+    #[serde(skip)]
+    pub(super) equivalence: Equivalence,
+    /// Ids of the games played so far, one per round.
+    pub(super) games: Vec<u32>,
+    /// The last game of the match this one is a rematch of.
+    pub(super) previous_game: Option<u32>,
+    /// Members of both teams in a team match, `players` holds the first member of each.
+    pub(super) teams: Option<[Vec<u32>; 2]>,
+    /// Display names of the players by id when the match started, players without one are left out.
+    pub(super) names: BTreeMap<u32, String>,
+    /// Usernames of the players who logged in with an account, guests are left out.
+    pub(super) usernames: BTreeMap<u32, String>,
+    pub(super) wins: [u16; 2],
+    /// Sum of the points of every round won.
+    pub(super) score: [u32; 2],
+    pub(super) result: Option<MatchResult>,
+}
+
+impl Match {
+    pub(super) fn new(id: u32, players: [u32; 2], options: MatchOptions, equivalence: Equivalence) -> Self {
+        Self {
+            id,
+            players,
+            options,
+            equivalence,
+            games: Vec::new(),
+            previous_game: None,
+            teams: None,
+            names: BTreeMap::new(),
+            usernames: BTreeMap::new(),
+            wins: [0; 2],
+            score: [0; 2],
+            result: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    fn index_of(&self, player_id: u32) -> Option<usize> {
+        match &self.teams {
+            Some(teams) => teams.iter().position(|team| team.contains(&player_id)),
+            None => self.players.iter().position(|id| *id == player_id),
+        }
+    }
+
+    /// Everyone playing in the match, both teams in a team match.
+    pub(super) fn members(&self) -> Vec<u32> {
+        match &self.teams {
+            Some(teams) => teams.iter().flatten().copied().collect(),
+            None => self.players.to_vec(),
+        }
+    }
+
+    /// Hinter and guesser of the next round.
+    pub(super) fn next_roles(&self) -> (u32, u32) {
+        let [first, second] = self.players;
+        if self.games.len().is_multiple_of(2) {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+
+    /// Count a finished round, returns whether the match is over after it.
+    /// A player leaving or an admin abort ends the match right away.
+    pub(super) fn record_round(&mut self, game: &Game) -> bool {
+        if let Some(i) = game.winner.and_then(|winner| self.index_of(winner)) {
+            self.wins[i] += 1;
+            self.score[i] += game.score;
+        }
+
+        match game.end_reason {
+            Some(EndReason::Disconnected) => self.finish(game.winner),
+            Some(EndReason::AbortedByAdmin) => self.finish(None),
+            _ => {
+                let needed = self.options.rounds / 2 + 1;
+                if self.wins.iter().any(|wins| *wins >= needed) || self.games.len() >= self.options.rounds as usize {
+                    self.finish(self.leader());
+                }
+            },
+        }
+
+        self.is_finished()
+    }
+
+    /// The player ahead on wins and then on points, `None` if they are level.
+    fn leader(&self) -> Option<u32> {
+        let standing = |i: usize| (self.wins[i], self.score[i]);
+        match standing(0).cmp(&standing(1)) {
+            Ordering::Greater => Some(self.players[0]),
+            Ordering::Less => Some(self.players[1]),
+            Ordering::Equal => None,
+        }
+    }
+
+    pub(super) fn finish(&mut self, winner: Option<u32>) {
+        self.result = Some(MatchResult {
+            winner,
+            wins: self.wins,
+            score: self.score,
+            timestamp: get_timestamp(),
+        });
+    }
+
+    /// Wins of the player and of their opponent.
+    fn wins_of(&self, player_id: u32) -> (u16, u16) {
+        match self.index_of(player_id) {
+            Some(0) => (self.wins[0], self.wins[1]),
+            _ => (self.wins[1], self.wins[0]),
+        }
+    }
+}
+
+/// A finished 1:1 match a player can ask to play again.
+struct Rematch {
+    opponent: u32,
+    options: MatchOptions,
+    /// The last game of the finished match, the rematch links to it.
+    last_game: u32,
+    last_hinter: u32,
+}
+
+impl Rematch {
+    /// The hinter of the first round, the one of the last game unless the player asked to swap the roles.
+    fn hinter(&self, player_id: u32, swap: bool) -> u32 {
+        match swap {
+            true if self.last_hinter == player_id => self.opponent,
+            true => player_id,
+            false => self.last_hinter,
+        }
+    }
+}
+
+impl ServerState {
+    /// The rematch the player can ask for after their last match, only for [`Server::REMATCH_GRACE`] seconds after it ended.
+    fn rematch_of(&self, player_id: u32, last_match: Option<u32>, now: u64) -> Result<Rematch, &'static str> {
+        let rematch = last_match.and_then(|match_id| {
+            let series = self.matches.get(&match_id).filter(|series| series.teams.is_none())?;
+            let ended = series.result.as_ref()?.timestamp;
+            let opponent = series.players.into_iter().find(|id| *id != player_id)?;
+            let last_game = *series.games.last()?;
+            let last_hinter = self.games.get(&last_game)?.read().unwrap().hinter;
+            Some((ended, Rematch { opponent, options: series.options.clone(), last_game, last_hinter }))
+        });
+
+        match rematch {
+            None => Err("No finished match to replay"),
+            Some((ended, _)) if now > ended + Server::REMATCH_GRACE => Err("Too late for a rematch"),
+            Some((_, rematch)) => Ok(rematch),
+        }
+    }
+
+    /// Whether the opponent already asked the player for the same rematch.
+    fn rematch_requested(&self, rematch: &Rematch, player_id: u32) -> bool {
+        self.invites.get(&rematch.opponent)
+            .is_some_and(|invite| invite.to == player_id && invite.previous_game == Some(rematch.last_game))
+    }
+}
+
+impl Server {
+    /// Count a finished game for its match or room, then either start the next round with swapped roles or announce the result.
+    /// Takes the state lock, so the caller must not hold any lock.
+    pub(super) fn finish_round(state: &ARWServerState, game_id: u32) -> AppResult<()> {
+        let mut state = state.write().unwrap();
+
+        let Some(game) = state.games.get(&game_id).cloned() else {
+            return Ok(())
+        };
+        let game = game.read().unwrap();
+
+        if let Some(room_id) = game.room_id.filter(|_| game.is_finished()) {
+            return Self::finish_room_game(&mut state, room_id, &game)
+        }
+
+        let Some(match_id) = game.match_id.filter(|_| game.is_finished()) else {
+            return Ok(())
+        };
+        let Some(series) = state.matches.get_mut(&match_id) else {
+            return Ok(())
+        };
+
+        // every round is only counted once
+        if series.is_finished() || series.games.last() != Some(&game_id) {
+            return Ok(())
+        }
+
+        let mut over = series.record_round(&game);
+        drop(game);
+
+        let players = series.members();
+        let available = players.iter().all(|id| {
+            state.players.get(id).is_some_and(|player| !player.read().unwrap().is_busy())
+        });
+
+        if !over && !available {
+            let series = state.matches.get_mut(&match_id).expect("match exists");
+            series.finish(series.leader());
+            over = true;
+        }
+
+        let series = &state.matches[&match_id];
+        let multi_round = series.options.rounds > 1;
+        let winner = series.result.as_ref().and_then(|result| result.winner);
+
+        for player_id in players {
+            let Some(player) = state.players.get(&player_id) else {
+                continue
+            };
+            let mut player = player.write().unwrap();
+
+            let mut notices = Vec::new();
+            if multi_round {
+                let (own, opponent) = series.wins_of(player_id);
+                notices.push(Command::MatchScore(own, opponent));
+            }
+            if over {
+                player.last_match = Some(match_id);
+                if multi_round {
+                    notices.push(Command::MatchOver(winner.map(|id| id.to_string()).unwrap_or_default()));
+                }
+            }
+
+            for notice in notices {
+                if let Err(err) = Self::send(&mut player.connection, notice) {
+                    eprintln!("Failed to notify player {}: {:?}", player_id, err);
+                }
+            }
+        }
+
+        if over {
+            Self::broadcast_match(&state, series)?;
+            return Self::finish_tournament_match(&mut state, match_id, winner)
+        }
+
+        Self::start_round(&mut state, match_id)
+    }
+
+    /// Create the game for the next round and ask its hinter for a word.
+    /// The guessers are told who the hinter is, the teammates of the hinter who the guesser is.
+    /// Everyone is sent the names of the other players of the game first.
+    pub(super) fn start_round(state: &mut ServerState, match_id: u32) -> AppResult<()> {
+        let game = state.create_round(match_id);
+        let game = game.read().unwrap();
+        let players = game.notified_players();
+
+        for player_id in &players {
+            if let Some(player) = state.players.get(player_id) {
+                player.write().unwrap().in_game = Some(game.id);
+            }
+        }
+
+        // everyone is in the game before anyone is told, so a player who cannot be reached does not hold up the others
+        for &player_id in &players {
+            let notice = if player_id == game.hinter {
+                Command::RequestMatchGuess
+            } else if game.is_guesser(player_id) {
+                Command::PlayerJoined(game.hinter.to_string())
+            } else {
+                Command::PlayerJoined(game.guesser.to_string())
+            };
+
+            let others = players.iter().copied().filter(|id| *id != player_id);
+            let result = Self::send_names(state, player_id, others)
+                .and_then(|()| Self::send_to(state, player_id, notice));
+            if let Err(err) = result {
+                eprintln!("Failed to notify player {}: {:?}", player_id, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ask the opponent of the last match for a rematch with the same options, or accept the rematch they asked for.
+    /// Only possible for [`Self::REMATCH_GRACE`] seconds after the match ended, the roles of its last game are kept unless `swap` is set.
+    pub(super) fn rematch(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, swap: bool) -> AppResult<()> {
+        let (self_id, last_match, busy) = {
+            let player = player.read().unwrap();
+            (player.id, player.last_match, player.is_busy())
+        };
+
+        if busy {
+            return Self::send_error(player, "Cannot request a rematch while playing")
+        }
+
+        let rematch = state.read().unwrap().rematch_of(self_id, last_match, get_timestamp());
+        let rematch = match rematch {
+            Ok(rematch) => rematch,
+            Err(err) => return Self::send_error(player, err),
+        };
+        let opponent = rematch.opponent;
+
+        // both players asking for a rematch is the same as accepting it
+        let requested = state.read().unwrap().rematch_requested(&rematch, self_id);
+        if requested {
+            return Self::accept_match(player, state, config, opponent.to_string())
+        }
+
+        let available = {
+            let state = state.read().unwrap();
+            state.players.get(&opponent).is_some_and(|player| !player.read().unwrap().is_busy())
+        };
+        if !available {
+            return Self::send_error(player, "Player is not available")
+        }
+
+        let hinter = rematch.hinter(self_id, swap);
+        let previous = state.write().unwrap().invites.insert(self_id, Invite::rematch(opponent, rematch.options, hinter, rematch.last_game));
+
+        let state = state.read().unwrap();
+        if let Some(previous) = previous {
+            if previous.to != opponent {
+                Self::notify(&state, previous.to, Command::CancelMatch(self_id.to_string()));
+            }
+        }
+
+        let result = Self::send_names(&state, opponent, [self_id])
+            .and_then(|()| Self::send_to(&state, opponent, Command::RematchRequest(self_id.to_string(), swap)));
+        if let Err(err) = result {
+            eprintln!("Failed to notify player {}: {:?}", opponent, err);
+        }
+
+        Ok(())
+    }
+
+    fn broadcast_match(state: &ServerState, series: &Match) -> AppResult<()> {
+        let json = serde_json::to_string(series).map_err(AppError::Serde)?;
+
+        let mut subs = state.subscribers.lock().unwrap();
+        for (_, conns) in subs.iter_mut() {
+            if let Some(conn) = conns {
+                if Self::send(conn, Command::MatchResult(json.clone())).is_err() {
+                    *conns = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use super::*;
+
+    fn best_of(rounds: u16) -> Match {
+        let options = MatchOptions {
+            rounds,
+            ..Default::default()
+        };
+        Match::new(1, [10, 20], options, Equivalence::default())
+    }
+
+    /// Play the next round of the match, the game ends for the reason with the winner getting `score` points.
+    fn play(series: &mut Match, winner: Option<u32>, reason: EndReason, score: u32) -> bool {
+        let (hinter, guesser) = series.next_roles();
+        let mut game = Game::new(series.games.len() as u32 + 100, hinter, guesser, series.options.clone(), Equivalence::default());
+        game.finish(winner, reason).unwrap();
+        game.score = score;
+
+        series.games.push(game.id);
+        series.record_round(&game)
+    }
+
+    #[test]
+    fn test_best_of() {
+        let mut series = best_of(3);
+        assert_eq!(series.next_roles(), (10, 20));
+
+        assert!(!play(&mut series, Some(20), EndReason::Guessed, 4));
+        // the roles swap every round
+        assert_eq!(series.next_roles(), (20, 10));
+        assert!(!play(&mut series, Some(10), EndReason::Guessed, 2));
+        assert_eq!((series.wins, series.score), ([1, 1], [2, 4]));
+        assert_eq!(series.wins_of(20), (1, 1));
+
+        assert!(play(&mut series, Some(10), EndReason::TimedOut, 1));
+        let result = series.result.as_ref().unwrap();
+        assert_eq!((result.winner, result.wins, result.score), (Some(10), [2, 1], [3, 4]));
+    }
+
+    #[test]
+    fn test_early_finish() {
+        // two wins out of three decide the match before the last round
+        let mut series = best_of(3);
+        assert!(!play(&mut series, Some(20), EndReason::Guessed, 1));
+        assert!(play(&mut series, Some(20), EndReason::Guessed, 1));
+        assert_eq!(series.result.as_ref().unwrap().winner, Some(20));
+
+        // a player leaving hands the match to the other one right away
+        let mut series = best_of(5);
+        assert!(!play(&mut series, Some(10), EndReason::Guessed, 3));
+        assert!(play(&mut series, Some(20), EndReason::Disconnected, 0));
+        assert_eq!(series.result.as_ref().unwrap().winner, Some(20));
+
+        // an aborted match has no winner
+        let mut series = best_of(5);
+        assert!(!play(&mut series, Some(10), EndReason::Guessed, 3));
+        assert!(play(&mut series, None, EndReason::AbortedByAdmin, 0));
+        assert_eq!(series.result.as_ref().unwrap().winner, None);
+    }
+
+    #[test]
+    fn test_leader() {
+        // level on wins, the points decide
+        let mut series = best_of(2);
+        assert!(!play(&mut series, Some(10), EndReason::Guessed, 2));
+        assert!(play(&mut series, Some(20), EndReason::Guessed, 5));
+        assert_eq!(series.result.as_ref().unwrap().winner, Some(20));
+
+        // level on both is a draw
+        let mut series = best_of(2);
+        assert!(!play(&mut series, Some(10), EndReason::Guessed, 3));
+        assert!(play(&mut series, Some(20), EndReason::Guessed, 3));
+        assert_eq!(series.result.as_ref().unwrap().winner, None);
+
+        // a round without a winner still counts
+        let mut series = best_of(1);
+        assert!(play(&mut series, None, EndReason::TimedOut, 0));
+        assert_eq!(series.result.as_ref().unwrap().wins, [0, 0]);
+    }
+
+    #[test]
+    fn test_rematch() {
+        let mut state = ServerState::new();
+        let mut series = best_of(1);
+        series.games = vec![5, 6];
+        series.finish(Some(10));
+        let ended = series.result.as_ref().unwrap().timestamp;
+        state.matches.insert(1, series);
+        state.games.insert(6, Arc::new(RwLock::new(Game::new(6, 20, 10, MatchOptions::default(), Equivalence::default()))));
+
+        let rematch = state.rematch_of(10, Some(1), ended + Server::REMATCH_GRACE).unwrap();
+        assert_eq!((rematch.opponent, rematch.last_game), (20, 6));
+        assert_eq!(state.rematch_of(10, Some(1), ended + Server::REMATCH_GRACE + 1).err(), Some("Too late for a rematch"));
+        assert_eq!(state.rematch_of(10, None, ended).err(), Some("No finished match to replay"));
+
+        // the hinter of the last game starts again unless the roles are swapped
+        assert_eq!(rematch.hinter(10, false), 20);
+        assert_eq!(rematch.hinter(10, true), 10);
+        assert_eq!(state.rematch_of(20, Some(1), ended).unwrap().hinter(20, true), 10);
+
+        // the opponent asking for the same rematch first makes it an accept
+        assert!(!state.rematch_requested(&rematch, 10));
+        state.invites.insert(20, Invite::rematch(10, MatchOptions::default(), 10, 6));
+        assert!(state.rematch_requested(&rematch, 10));
+        state.invites.insert(20, Invite::rematch(10, MatchOptions::default(), 10, 4));
+        assert!(!state.rematch_requested(&rematch, 10));
+
+        // matches which are not over and team matches cannot be replayed
+        state.matches.get_mut(&1).unwrap().result = None;
+        assert!(state.rematch_of(10, Some(1), ended).is_err());
+        let series = state.matches.get_mut(&1).unwrap();
+        series.finish(Some(10));
+        series.teams = Some([vec![10], vec![20]]);
+        assert!(state.rematch_of(10, Some(1), ended).is_err());
+    }
+}
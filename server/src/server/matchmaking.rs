@@ -0,0 +1,141 @@
+use std::{collections::{HashSet, VecDeque}, sync::Arc};
+
+use crate::{AppResult, Command};
+
+use super::{AMPlayer, ARWServerState, MatchOptions, Server, ServerConfig, ServerState};
+
+/// First come, first served queue of players waiting for an automatically assigned opponent.
+/// Every player waits with the language they want to play in, `None` if any language is fine.
+#[derive(Default)]
+pub struct Matchmaker {
+    queue: VecDeque<(u32, Option<String>)>,
+}
+
+impl Matchmaker {
+    /// Add a player to the end of the queue, returns false if they are already waiting.
+    fn join(&mut self, player_id: u32, language: Option<String>) -> bool {
+        if self.queue.iter().any(|(id, _)| *id == player_id) {
+            return false
+        }
+
+        self.queue.push_back((player_id, language));
+        true
+    }
+
+    /// Remove a player from the queue, returns false if they were not waiting.
+    pub(super) fn leave(&mut self, player_id: u32) -> bool {
+        let len = self.queue.len();
+        self.queue.retain(|(id, _)| *id != player_id);
+        self.queue.len() != len
+    }
+
+    /// Take the player who has been waiting the longest together with the next one who shares their language.
+    /// Players for whom `available` is false, for example because they joined a room meanwhile, keep their place but are skipped.
+    /// Returns the language of the match, `None` if neither of them cares.
+    fn take_pair(&mut self, available: impl Fn(u32) -> bool) -> Option<(u32, u32, Option<String>)> {
+        let waiting = (0..self.queue.len()).filter(|i| available(self.queue[*i].0)).collect::<Vec<_>>();
+        let (i, j) = waiting.iter().enumerate().find_map(|(n, i)| {
+            let language = &self.queue[*i].1;
+            let j = waiting[n + 1..].iter().copied().find(|j| shares_language(language, &self.queue[*j].1))?;
+            Some((*i, j))
+        })?;
+
+        let (second, second_language) = self.queue.remove(j)?;
+        let (first, first_language) = self.queue.remove(i)?;
+        Some((first, second, first_language.or(second_language)))
+    }
+}
+
+/// Whether two players can play together, a player without a language plays in any.
+pub(super) fn shares_language(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+impl Server {
+    pub(super) fn join_queue(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>) -> AppResult<()> {
+        let (self_id, busy, language) = {
+            let player = player.read().unwrap();
+            (player.id, player.is_busy(), player.language.clone())
+        };
+
+        if busy {
+            return Self::send_error(player, "Cannot join the queue while playing")
+        }
+
+        if !state.write().unwrap().matchmaker.join(self_id, language) {
+            return Self::send_error(player, "Already in the queue")
+        }
+
+        Self::matchmake(state, config)
+    }
+
+    pub(super) fn leave_queue(player: &AMPlayer, state: &ARWServerState) -> AppResult<()> {
+        let self_id = player.read().unwrap().id;
+
+        if !state.write().unwrap().matchmaker.leave(self_id) {
+            return Self::send_error(player, "Not in the queue")
+        }
+
+        Self::send(&mut player.write().unwrap().connection, Command::Ok)
+    }
+
+    /// Start games for waiting players until nobody left in the queue shares a language.
+    /// Roles are assigned randomly and the default options in the shared language are used, the hinter is asked for a word like after an accepted invite.
+    fn matchmake(state: &ARWServerState, config: &Arc<ServerConfig>) -> AppResult<()> {
+        let mut state = state.write().unwrap();
+
+        // whoever disconnected while waiting is dropped, the others keep their place in line
+        let connected = state.players.keys().copied().collect::<HashSet<_>>();
+        state.matchmaker.queue.retain(|(id, _)| connected.contains(id));
+
+        loop {
+            let ServerState { players, matchmaker, .. } = &mut *state;
+            let Some((first_id, second_id, language)) = matchmaker.take_pair(|id| !players[&id].read().unwrap().is_busy()) else {
+                break
+            };
+
+            let (hinter, guesser) = if rand::random() {
+                (first_id, second_id)
+            } else {
+                (second_id, first_id)
+            };
+
+            let mut options = config.apply_defaults(MatchOptions::default());
+            if let Some(language) = language {
+                options.language = language;
+            }
+            let match_id = state.create_match(hinter, guesser, options, config.equivalence, None);
+            if let Err(err) = Self::start_round(&mut state, match_id) {
+                eprintln!("Failed to notify matched players: {:?}", err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_pair() {
+        let mut matchmaker = Matchmaker::default();
+        matchmaker.join(1, Some("de".to_string()));
+        matchmaker.join(2, Some("fr".to_string()));
+        assert!(!matchmaker.join(2, None));
+        assert_eq!(matchmaker.take_pair(|_| true), None);
+
+        matchmaker.join(3, None);
+        matchmaker.join(4, Some("fr".to_string()));
+        assert_eq!(matchmaker.take_pair(|id| id != 1), Some((2, 3, Some("fr".to_string()))));
+        assert_eq!(matchmaker.take_pair(|_| true), None);
+
+        matchmaker.join(5, None);
+        assert_eq!(matchmaker.take_pair(|_| true), Some((1, 5, Some("de".to_string()))));
+        assert_eq!(matchmaker.take_pair(|_| true), None);
+    }
+}
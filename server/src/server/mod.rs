@@ -1,20 +1,74 @@
+mod accounts;
+mod bots;
 mod clients;
+mod dictionary;
+mod events;
+mod game;
+mod history;
+mod invites;
 mod listeners;
-
-use std::{collections::HashMap, fs, io::{stdin, BufRead, Read}, sync::{Arc, Mutex, RwLock}};
-
-use serde::Serialize;
+mod matches;
+mod matchmaking;
+mod options;
+mod retention;
+mod rooms;
+mod rules;
+mod solo;
+mod teams;
+mod timers;
+mod tournaments;
+mod word;
+mod words;
+
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, io::{stdin, BufRead, Read}, sync::{Arc, Mutex, RwLock}, time::{SystemTime, UNIX_EPOCH}};
 
 use crate::{AppError, AppResult, Command, Connection, Packet, ReadBytes, WriteBytes};
 
+pub use accounts::*;
+pub use dictionary::*;
+pub use events::*;
+pub use game::*;
+pub use history::*;
+pub use invites::*;
+pub use matches::*;
+pub use matchmaking::*;
+pub use options::*;
+pub use retention::*;
+pub use rooms::*;
+pub use rules::*;
+pub use solo::*;
+pub use teams::*;
+pub use tournaments::*;
+pub use word::*;
+pub use words::*;
+
 type AMPlayer = Arc<RwLock<Player>>;
+type AMGame = Arc<RwLock<Game>>;
 type ARWServerState = Arc<RwLock<ServerState>>;
 
+fn get_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("time travel").as_secs()
+}
+
 pub struct Player {
     id: u32,
     connection: Connection,
     in_game: Option<u32>,
+    in_room: Option<u32>,
+    in_lobby: Option<u32>,
+    /// The most recently finished match, used for rematches.
+    last_match: Option<u32>,
+    /// The language the player wants to play in, `None` for any.
+    language: Option<String>,
+    /// The username of the account the player logged in with, `None` for players who sent the server password.
+    account: Option<String>,
+    /// The name chosen with [`Command::SetName`].
+    name: Option<String>,
+    /// Refused logins and registrations, the connection is closed after [`Server::MAX_LOGIN_ATTEMPTS`].
+    failed_logins: u8,
     authenticated: bool,
+    /// Cleared when the connection closes, the player stays in [`ServerState::players`] until its thread is done.
+    connected: bool,
 }
 
 impl Player {
@@ -23,37 +77,26 @@ impl Player {
             id,
             connection,
             in_game: None,
+            in_room: None,
+            in_lobby: None,
+            last_match: None,
+            language: None,
+            account: None,
+            name: None,
+            failed_logins: 0,
             authenticated: false,
+            connected: true,
         }
     }
-}
 
-#[derive(Serialize, Debug)]
-pub struct Game {
-    id: u32,
-    hinter: u32,
-    guesser: u32,
-    word: Option<String>,
-    guesses: Vec<String>,
-    hints: Vec<String>,
-    winner: Option<u32>,
-    finished: bool,
-    timestamp: u64,
-}
+    /// Whether the player is playing a game, sitting in a room or waiting for a team match.
+    fn is_busy(&self) -> bool {
+        self.in_game.is_some() || self.in_room.is_some() || self.in_lobby.is_some()
+    }
 
-impl Game {
-    fn new(id: u32, hinter: u32, guesser: u32) -> Self {
-        Self {
-            id,
-            hinter,
-            guesser,
-            word: None,
-            guesses: Vec::new(),
-            hints: Vec::new(),
-            winner: None,
-            finished: false,
-            timestamp: 0,
-        }
+    /// The chosen name, or the username of the account without one.
+    fn display_name(&self) -> Option<&str> {
+        self.name.as_deref().or(self.account.as_deref())
     }
 }
 
@@ -61,8 +104,26 @@ pub struct ServerState {
     players: HashMap<u32, Arc<RwLock<Player>>>,
     subscribers: Arc<Mutex<HashMap<u32, Option<Connection>>>>,
     games: HashMap<u32, Arc<RwLock<Game>>>,
+    /// Ids of the games which may not be finished yet, the timers only look at these.
+    active_games: HashSet<u32>,
+    /// Ids of the running solo games, which the timers give hints in.
+    solo_games: HashSet<u32>,
+    matches: HashMap<u32, Match>,
+    rooms: HashMap<u32, Room>,
+    lobbies: HashMap<u32, TeamLobby>,
+    tournaments: HashMap<u32, Tournament>,
+    invites: HashMap<u32, Invite>,
+    matchmaker: Matchmaker,
+    /// Where the events of every game are stored, `None` if they are only kept in memory.
+    history: Option<Arc<History>>,
+    retention: Retention,
+    accounts: Accounts,
     next_player_id: u32,
     next_game_id: u32,
+    next_match_id: u32,
+    next_room_id: u32,
+    next_lobby_id: u32,
+    next_tournament_id: u32,
 }
 
 impl ServerState {
@@ -71,8 +132,23 @@ impl ServerState {
             players: HashMap::new(),
             subscribers: Arc::new(Mutex::new(HashMap::new())),
             games: HashMap::new(),
+            active_games: HashSet::new(),
+            solo_games: HashSet::new(),
+            matches: HashMap::new(),
+            rooms: HashMap::new(),
+            lobbies: HashMap::new(),
+            tournaments: HashMap::new(),
+            invites: HashMap::new(),
+            matchmaker: Matchmaker::default(),
+            history: None,
+            retention: Retention::default(),
+            accounts: Accounts::default(),
             next_player_id: 1,
             next_game_id: 1,
+            next_match_id: 1,
+            next_room_id: 1,
+            next_lobby_id: 1,
+            next_tournament_id: 1,
         }
     }
 
@@ -88,6 +164,30 @@ impl ServerState {
         id
     }
 
+    fn next_match_id(&mut self) -> u32 {
+        let id = self.next_match_id;
+        self.next_match_id += 1;
+        id
+    }
+
+    fn next_room_id(&mut self) -> u32 {
+        let id = self.next_room_id;
+        self.next_room_id += 1;
+        id
+    }
+
+    fn next_lobby_id(&mut self) -> u32 {
+        let id = self.next_lobby_id;
+        self.next_lobby_id += 1;
+        id
+    }
+
+    fn next_tournament_id(&mut self) -> u32 {
+        let id = self.next_tournament_id;
+        self.next_tournament_id += 1;
+        id
+    }
+
     fn create_player(&mut self, connection: Connection) -> Arc<RwLock<Player>> {
         let id = self.next_player_id();
         let player = Player::new(id, connection);
@@ -96,24 +196,297 @@ impl ServerState {
         player
     }
 
-    fn create_game(&mut self, hinter: u32, guesser: u32) -> Arc<RwLock<Game>> {
+    fn create_game(&mut self, hinter: u32, guesser: u32, options: MatchOptions, equivalence: Equivalence) -> Arc<RwLock<Game>> {
+        let game = self.new_game(hinter, guesser, options, equivalence);
+        self.add_game(game)
+    }
+
+    /// A game which is not added yet, whoever creates it finishes the setup and passes it to [`ServerState::add_game`].
+    fn new_game(&mut self, hinter: u32, guesser: u32, options: MatchOptions, equivalence: Equivalence) -> Game {
         let id = self.next_game_id();
-        let game = Game::new(id, hinter, guesser);
+        let mut game = Game::new(id, hinter, guesser, options, equivalence);
+        game.log = self.history.clone();
+        game.names = self.names([hinter, guesser]);
+        game.usernames = self.usernames([hinter, guesser]);
+        game
+    }
+
+    /// Add a game once it is set up, its setup is recorded as the first event.
+    fn add_game(&mut self, mut game: Game) -> Arc<RwLock<Game>> {
+        game.record_creation();
+        let id = game.id;
         let game = Arc::new(RwLock::new(game));
         self.games.insert(id, game.clone());
+        self.active_games.insert(id);
         game
     }
+
+    /// Create a match without any rounds yet, the first player is the hinter of the first round.
+    /// A rematch is linked to the last game of the previous match.
+    fn create_match(&mut self, hinter: u32, guesser: u32, options: MatchOptions, equivalence: Equivalence, previous_game: Option<u32>) -> u32 {
+        let id = self.next_match_id();
+        let mut series = Match::new(id, [hinter, guesser], options, equivalence);
+        series.previous_game = previous_game;
+        series.names = self.names([hinter, guesser]);
+        series.usernames = self.usernames([hinter, guesser]);
+        self.matches.insert(id, series);
+        id
+    }
+
+    /// Create a match between two teams, the first member of each team stands in for it as hinter or guesser.
+    fn create_team_match(&mut self, teams: [Vec<u32>; 2], options: MatchOptions, equivalence: Equivalence) -> u32 {
+        let id = self.next_match_id();
+        let mut series = Match::new(id, [teams[0][0], teams[1][0]], options, equivalence);
+        series.names = self.names(teams.iter().flatten().copied());
+        series.usernames = self.usernames(teams.iter().flatten().copied());
+        series.teams = Some(teams);
+        self.matches.insert(id, series);
+        id
+    }
+
+    /// Open a team lobby with the owner in the first team.
+    fn create_lobby(&mut self, owner: u32, options: MatchOptions) -> u32 {
+        let id = self.next_lobby_id();
+        self.lobbies.insert(id, TeamLobby::new(id, owner, options));
+        id
+    }
+
+    /// Open a tournament for registration.
+    fn create_tournament(&mut self, format: TournamentFormat, options: MatchOptions, equivalence: Equivalence) -> u32 {
+        let id = self.next_tournament_id();
+        self.tournaments.insert(id, Tournament::new(id, format, options, equivalence));
+        id
+    }
+
+    /// Open a room without any guessers yet.
+    fn create_room(&mut self, hinter: u32, options: MatchOptions, equivalence: Equivalence) -> u32 {
+        let id = self.next_room_id();
+        self.rooms.insert(id, Room::new(id, hinter, options, equivalence));
+        id
+    }
+
+    /// Create the game for the next round of a match.
+    fn create_round(&mut self, match_id: u32) -> Arc<RwLock<Game>> {
+        let series = &self.matches[&match_id];
+        let (hinter, guesser) = series.next_roles();
+        let mut game = self.new_game(hinter, guesser, series.options.clone(), series.equivalence);
+
+        let series = self.matches.get_mut(&match_id).expect("match exists");
+        game.previous_game = series.games.last().copied().or(series.previous_game);
+        series.games.push(game.id);
+        game.match_id = Some(match_id);
+        game.round = series.games.len() as u16;
+
+        if let Some(teams) = &series.teams {
+            let order = if hinter == series.players[0] { [0, 1] } else { [1, 0] };
+            game.teams = order.map(|i| Team::new(teams[i].clone(), series.score[i])).to_vec();
+        }
+
+        // every member of the teams is named, not only the hinter and the guesser
+        game.names = self.names(game.notified_players());
+        game.usernames = self.usernames(game.notified_players());
+        self.add_game(game)
+    }
+
+    /// The display names of the players by id, players without one are left out.
+    fn names(&self, ids: impl IntoIterator<Item = u32>) -> BTreeMap<u32, String> {
+        ids.into_iter()
+            .filter_map(|id| Some((id, self.players.get(&id)?.read().unwrap().display_name()?.to_string())))
+            .collect()
+    }
+
+    /// The usernames of the players by id, guests are left out.
+    fn usernames(&self, ids: impl IntoIterator<Item = u32>) -> BTreeMap<u32, String> {
+        ids.into_iter()
+            .filter_map(|id| Some((id, self.players.get(&id)?.read().unwrap().account.clone()?)))
+            .collect()
+    }
 }
 
 pub struct ServerConfig {
     password: String,
+    equivalence: Equivalence,
+    /// Wrong guesses allowed in matches which do not set their own limit.
+    max_wrong_guesses: Option<u16>,
+    /// Seconds per turn in matches which do not set their own limit, so nobody can stall a game forever.
+    turn_limit: Option<u16>,
+    /// Words the server picks from in solo games and bots guess from.
+    words: HashMap<String, WordList>,
+    /// Words with hints for bots which have to set the word, per language.
+    hints: HashMap<String, HintList>,
+    /// Bots added when the server starts.
+    bots: u16,
+    /// The JSON lines file the events of all games are stored in, `None` keeps them in memory only.
+    history: Option<String>,
+    /// Finished games kept in memory, `None` keeps all of them.
+    max_games: Option<usize>,
+    /// Seconds a finished game is kept in memory, `None` keeps it until there are too many.
+    max_game_age: Option<u64>,
+    /// The JSON lines file games removed from memory are appended to, `None` drops them.
+    archive: Option<String>,
+    /// The JSON lines file accounts are stored in, `None` keeps them in memory only.
+    accounts: Option<String>,
+    /// Words set by hinters have to be in the dictionary of the match language, reloaded from the server console.
+    dictionaries: RwLock<Dictionaries>,
 }
 
 impl ServerConfig {
-    fn new(password: String) -> Self {
-        Self {
-            password,
+    const DEFAULT_PASSWORD: &'static str = "supersecret123";
+    const DEFAULT_TURN_LIMIT: u16 = 300;
+    const DEFAULT_HISTORY: &'static str = "games.jsonl";
+    const DEFAULT_MAX_GAMES: usize = 1000;
+    const DEFAULT_ACCOUNTS: &'static str = "accounts.jsonl";
+
+    /// Parse `[PASSWORD] [--case-sensitive] [--ignore-diacritics] [--ignore-punctuation] [--max-wrong-guesses=N] [--turn-time=SECONDS] [--words=[LANG:]PATH...] [--hints=[LANG:]PATH...] [--bots=N] [--dictionary=LANG:PATH...] [--history=PATH] [--max-games=N] [--max-game-age=SECONDS] [--archive=PATH] [--accounts=PATH]`.
+    pub fn from_args(args: impl Iterator<Item = String>) -> AppResult<Self> {
+        let mut password = None;
+        let mut equivalence = Equivalence::default();
+        let mut max_wrong_guesses = None;
+        let mut turn_limit = Some(Self::DEFAULT_TURN_LIMIT);
+        let mut words = MatchOptions::LANGUAGES.iter()
+            .filter_map(|language| Some((language.to_string(), WordList::builtin(language)?)))
+            .collect::<HashMap<_, _>>();
+        let mut hints = MatchOptions::LANGUAGES.iter()
+            .filter_map(|language| Some((language.to_string(), HintList::builtin(language)?)))
+            .collect::<HashMap<_, _>>();
+        let mut bots = 0;
+        let mut dictionaries = Vec::new();
+        let mut history = Some(Self::DEFAULT_HISTORY.to_string());
+        let mut max_games = Some(Self::DEFAULT_MAX_GAMES);
+        let mut max_game_age = None;
+        let mut archive = None;
+        let mut accounts = Some(Self::DEFAULT_ACCOUNTS.to_string());
+
+        for arg in args {
+            if let Some(max) = arg.strip_prefix("--max-wrong-guesses=") {
+                match max.parse() {
+                    Ok(max) if max > 0 => max_wrong_guesses = Some(max),
+                    _ => return Err(AppError::InvalidArgument(arg)),
+                }
+                continue
+            }
+
+            // 0 turns the turn limit off
+            if let Some(time) = arg.strip_prefix("--turn-time=") {
+                match time.parse() {
+                    Ok(0) => turn_limit = None,
+                    Ok(time) if time <= MatchOptions::MAX_TIME_LIMIT => turn_limit = Some(time),
+                    _ => return Err(AppError::InvalidArgument(arg)),
+                }
+                continue
+            }
+
+            // a file replaces the built-in list of its language
+            if let Some(source) = arg.strip_prefix("--words=") {
+                let (language, path) = split_source_or_default(source)?;
+                words.insert(language, WordList::load(path)?);
+                continue
+            }
+
+            if let Some(source) = arg.strip_prefix("--hints=") {
+                let (language, path) = split_source_or_default(source)?;
+                hints.insert(language, HintList::load(path)?);
+                continue
+            }
+
+            // an empty path keeps the history in memory
+            if let Some(path) = arg.strip_prefix("--history=") {
+                history = (!path.is_empty()).then(|| path.to_string());
+                continue
+            }
+
+            // 0 keeps every game
+            if let Some(max) = arg.strip_prefix("--max-games=") {
+                match max.parse() {
+                    Ok(max) => max_games = (max > 0).then_some(max),
+                    _ => return Err(AppError::InvalidArgument(arg)),
+                }
+                continue
+            }
+
+            if let Some(age) = arg.strip_prefix("--max-game-age=") {
+                match age.parse() {
+                    Ok(age) => max_game_age = (age > 0).then_some(age),
+                    _ => return Err(AppError::InvalidArgument(arg)),
+                }
+                continue
+            }
+
+            if let Some(path) = arg.strip_prefix("--archive=") {
+                archive = (!path.is_empty()).then(|| path.to_string());
+                continue
+            }
+
+            // an empty path keeps the accounts in memory
+            if let Some(path) = arg.strip_prefix("--accounts=") {
+                accounts = (!path.is_empty()).then(|| path.to_string());
+                continue
+            }
+
+            if let Some(source) = arg.strip_prefix("--dictionary=") {
+                dictionaries.push(source.to_string());
+                continue
+            }
+
+            if let Some(count) = arg.strip_prefix("--bots=") {
+                match count.parse() {
+                    Ok(count) => bots = count,
+                    _ => return Err(AppError::InvalidArgument(arg)),
+                }
+                continue
+            }
+
+            match arg.as_str() {
+                "--case-sensitive" => equivalence.ignore_case = false,
+                "--ignore-diacritics" => equivalence.ignore_diacritics = true,
+                "--ignore-punctuation" => equivalence.ignore_punctuation = true,
+                flag if flag.starts_with("--") => return Err(AppError::InvalidArgument(arg)),
+                _ => password = Some(arg),
+            }
         }
+
+        // the words are folded like guesses, so the comparison flags have to be known first
+        let dictionaries = dictionaries.iter().try_fold(Dictionaries::new(equivalence), |mut dictionaries, source| {
+            dictionaries.add_source(source)?;
+            Ok::<_, AppError>(dictionaries)
+        })?;
+
+        Ok(Self {
+            password: password.unwrap_or_else(|| Self::DEFAULT_PASSWORD.to_string()),
+            equivalence,
+            max_wrong_guesses,
+            turn_limit,
+            words,
+            hints,
+            bots,
+            dictionaries: RwLock::new(dictionaries),
+            history,
+            max_games,
+            max_game_age,
+            archive,
+            accounts,
+        })
+    }
+
+    /// Fill in the server defaults for options the match did not set.
+    fn apply_defaults(&self, mut options: MatchOptions) -> MatchOptions {
+        options.max_wrong_guesses = options.max_wrong_guesses.or(self.max_wrong_guesses);
+        options.turn_limit = options.turn_limit.or(self.turn_limit);
+        options
+    }
+
+    /// A random word for a match with the options out of the word list of its language.
+    fn pick_word(&self, options: &MatchOptions) -> Option<String> {
+        self.words.get(&options.language)?.pick(|word| self.accepts(options, word))
+    }
+
+    /// Whether the server or a bot may set the word in a match with the options.
+    /// The rules, the allowed length and the dictionary of the language all have to accept it.
+    fn accepts(&self, options: &MatchOptions, word: &str) -> bool {
+        let rules = ruleset(&options.ruleset).unwrap_or(&Freeform);
+        options.allows_length(graphemes(word).len())
+            && rules.validate_word(word).is_ok()
+            && self.dictionaries.read().unwrap().check(&options.language, word).is_ok()
     }
 }
 
@@ -124,33 +497,110 @@ pub struct Server {
 
 impl Server {
     const SOCKET_PATH: &'static str = "/tmp/game-guess-a-word-socket";
+    /// Seconds after which a pending match invite expires.
+    const INVITE_TIMEOUT: u64 = 60;
+    /// Seconds after the end of a match in which a rematch can be requested.
+    const REMATCH_GRACE: u64 = 60;
+
+    /// Create the server with the games of its history, if it has one, as far as the retention keeps them.
+    pub fn new(config: ServerConfig) -> AppResult<Self> {
+        let mut state = ServerState::new();
+        state.retention = Retention {
+            max_games: config.max_games,
+            max_age: config.max_game_age,
+            archive: config.archive.as_deref().map(History::open).transpose()?,
+            ..Default::default()
+        };
 
-    pub fn new(password: String) -> Self {
-        Self {
-            state: Arc::new(RwLock::new(ServerState::new())),
-            config: Arc::new(ServerConfig::new(password)),
+        if let Some(path) = &config.accounts {
+            state.accounts = Accounts::load(path)?;
+            println!("Loaded {} accounts from {:?}", state.accounts.len(), path);
+        }
+
+        if let Some(path) = &config.history {
+            let count = state.restore_history(path)?;
+            let pruned = state.prune_games(get_timestamp()).len();
+            println!("Loaded {} games from {:?}, {} of them past the retention", count, path, pruned);
         }
+
+        Ok(Self {
+            state: Arc::new(RwLock::new(state)),
+            config: Arc::new(config),
+        })
     }
 
     pub fn run(&mut self) -> AppResult<()> {
         println!("Server started with password: {:?}", self.config.password);
+        println!("Words are compared with {:?}", self.config.equivalence);
+        if let Some(max) = self.config.max_wrong_guesses {
+            println!("Matches allow {} wrong guesses by default", max);
+        }
+        if let Some(time) = self.config.turn_limit {
+            println!("Turns time out after {} seconds by default", time);
+        }
+        if let Some(max) = self.config.max_games {
+            println!("Keeping the last {} finished games in memory", max);
+        }
+        if let Some(age) = self.config.max_game_age {
+            println!("Keeping finished games in memory for {} seconds", age);
+        }
+        if let Some(path) = &self.config.archive {
+            println!("Archiving older games to {:?}", path);
+        }
+        for (language, size) in self.config.dictionaries.read().unwrap().sizes() {
+            println!("Words in {} have to be in its dictionary of {} words", language, size);
+        }
+        for language in MatchOptions::LANGUAGES {
+            let words = self.config.words.get(language).map_or(0, WordList::len);
+            let hints = self.config.hints.get(language).map_or(0, HintList::len);
+            println!("Solo games in {} pick from {} words, bots hint with {} words", language, words, hints);
+        }
         self.start_unix_listener()?; 
         self.start_tcp_listener()?; 
+        self.start_timers();
+
+        for _ in 0..self.config.bots {
+            self.add_bot_from_console()?;
+        }
 
         for line in stdin().lock().lines() {
             let line = line?;
 
-            if line.trim() == "exit" {
+            let line = line.trim();
+
+            if line == "exit" {
                 break;
+            } else if let Some(id) = line.strip_prefix("abort ") {
+                match id.trim().parse() {
+                    Ok(game_id) => Self::abort_game(&self.state, game_id)?,
+                    Err(_) => println!("Invalid game id: {:?}", id),
+                }
+            } else if line == "reload" {
+                Self::reload_dictionaries(&self.config);
+            } else if line == "bot" {
+                self.add_bot_from_console()?;
+            } else if let Some(args) = line.strip_prefix("tournament ") {
+                Self::create_tournament(&self.state, &self.config, args);
+            } else if let Some(id) = line.strip_prefix("start ") {
+                match id.trim().parse() {
+                    Ok(tournament_id) => Self::start_tournament(&self.state, tournament_id),
+                    Err(_) => println!("Invalid tournament id: {:?}", id),
+                }
             } else {
                 println!("Unknown command: {:?}", line);
-                println!("Type 'exit' to quit");
+                println!("Type 'exit' to quit, 'abort [game_id]' to end a game, 'bot' to add a bot player, 'reload' to read the dictionaries again, 'tournament [elimination|round-robin] [option=value...]' to open a tournament or 'start [tournament_id]' to start it");
             }
         }
 
         Ok(())
     }
 
+    fn add_bot_from_console(&self) -> AppResult<()> {
+        let id = Self::add_bot(&self.state, &self.config)?;
+        println!("Added bot player {}", id);
+        Ok(())
+    }
+
     /// Unlink the socket file if it exists.
     pub fn cleanup_socket() {
         if fs::remove_file(Self::SOCKET_PATH).is_ok() {
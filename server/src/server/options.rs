@@ -0,0 +1,237 @@
+use std::{fmt, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AppResult, ReadBytes, WriteBytes};
+
+use super::{ruleset, ruleset_names};
+
+/// Settings of a single match, proposed by the inviting player and accepted together with the invite.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MatchOptions {
+    pub ruleset: String,
+    pub language: String,
+    pub max_wrong_guesses: Option<u16>,
+    /// Seconds the guesser has to find the word once it is set.
+    pub time_limit: Option<u16>,
+    /// Seconds each player has for their next action, setting the word or guessing.
+    pub turn_limit: Option<u16>,
+    pub hint_limit: Option<u16>,
+    pub min_word_length: Option<u16>,
+    pub max_word_length: Option<u16>,
+    /// Number of rounds in a best-of match, the roles swap every round.
+    pub rounds: u16,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            ruleset: "freeform".to_string(),
+            language: "lb".to_string(),
+            max_wrong_guesses: None,
+            time_limit: None,
+            turn_limit: None,
+            hint_limit: None,
+            min_word_length: None,
+            max_word_length: None,
+            rounds: 1,
+        }
+    }
+}
+
+impl MatchOptions {
+    pub const MAX_TIME_LIMIT: u16 = 3600;
+    pub const MAX_ROUNDS: u16 = 15;
+    /// Languages a match can be played in, every one has a built-in word list.
+    pub const LANGUAGES: [&'static str; 4] = ["lb", "de", "fr", "en"];
+
+    /// Set an option from its `key=value` form, like `guesses=6` or `length=4-8`.
+    pub fn apply(&mut self, option: &str) -> Result<(), String> {
+        let Some((key, value)) = option.split_once('=') else {
+            return Err(format!("Expected key=value, got {:?}", option))
+        };
+
+        let number = |value: &str| value.parse::<u16>()
+            .map_err(|_| format!("Invalid number for {}: {:?}", key, value));
+
+        match key {
+            "rules" => self.ruleset = value.to_string(),
+            "lang" => self.language = value.to_string(),
+            "guesses" => self.max_wrong_guesses = Some(number(value)?),
+            "time" => self.time_limit = Some(number(value)?),
+            "turn" => self.turn_limit = Some(number(value)?),
+            "hints" => self.hint_limit = Some(number(value)?),
+            "rounds" => self.rounds = number(value)?,
+            "length" => {
+                let (min, max) = value.split_once('-').unwrap_or((value, value));
+                self.min_word_length = (!min.is_empty()).then(|| number(min)).transpose()?;
+                self.max_word_length = (!max.is_empty()).then(|| number(max)).transpose()?;
+            },
+            _ => return Err(format!("Unknown option {:?}, available: rules, lang, guesses, time, turn, hints, length, rounds", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Fill in the default ruleset if the client sent none, then check the options like [`MatchOptions::validate`].
+    pub fn normalize(&mut self) -> Result<(), String> {
+        if self.ruleset.is_empty() {
+            self.ruleset = Self::default().ruleset;
+        }
+
+        self.validate()
+    }
+
+    /// Check the options before an invite is sent, the invited player only sees valid options.
+    pub fn validate(&self) -> Result<(), String> {
+        if ruleset(&self.ruleset).is_none() {
+            return Err(format!("Unknown ruleset, available: {}", ruleset_names().join(", ")))
+        }
+
+        if !Self::LANGUAGES.contains(&self.language.as_str()) {
+            return Err(format!("Unknown language, available: {}", Self::LANGUAGES.join(", ")))
+        }
+
+        if self.max_wrong_guesses == Some(0) {
+            return Err("At least one wrong guess has to be allowed".to_string())
+        }
+
+        if self.time_limit.is_some_and(|time| !(10..=Self::MAX_TIME_LIMIT).contains(&time)) {
+            return Err(format!("Time limit has to be between 10 and {} seconds", Self::MAX_TIME_LIMIT))
+        }
+
+        if self.turn_limit.is_some_and(|time| !(5..=Self::MAX_TIME_LIMIT).contains(&time)) {
+            return Err(format!("Turn limit has to be between 5 and {} seconds", Self::MAX_TIME_LIMIT))
+        }
+
+        if !(1..=Self::MAX_ROUNDS).contains(&self.rounds) {
+            return Err(format!("A match has between 1 and {} rounds", Self::MAX_ROUNDS))
+        }
+
+        if self.min_word_length == Some(0) {
+            return Err("Minimum word length has to be at least 1".to_string())
+        }
+
+        if let (Some(min), Some(max)) = (self.min_word_length, self.max_word_length) {
+            if min > max {
+                return Err("Minimum word length is larger than the maximum".to_string())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the length of a word in graphemes.
+    pub fn allows_length(&self, length: usize) -> bool {
+        self.min_word_length.is_none_or(|min| length >= min as usize)
+            && self.max_word_length.is_none_or(|max| length <= max as usize)
+    }
+}
+
+impl fmt::Display for MatchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.ruleset, self.language)?;
+
+        if self.rounds > 1 {
+            write!(f, ", best of {}", self.rounds)?;
+        }
+
+        if let Some(guesses) = self.max_wrong_guesses {
+            write!(f, ", {} wrong guesses", guesses)?;
+        }
+        if let Some(time) = self.time_limit {
+            write!(f, ", {}s time limit", time)?;
+        }
+        if let Some(turn) = self.turn_limit {
+            write!(f, ", {}s per turn", turn)?;
+        }
+        if let Some(hints) = self.hint_limit {
+            write!(f, ", {} hints", hints)?;
+        }
+        match (self.min_word_length, self.max_word_length) {
+            (None, None) => (),
+            (min, max) => write!(f, ", word length {}-{}", min.unwrap_or(1), max.map(|max| max.to_string()).unwrap_or_default())?,
+        }
+
+        Ok(())
+    }
+}
+
+impl WriteBytes for MatchOptions {
+    fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
+        let n = [
+            self.ruleset.write(buffer)?,
+            self.language.write(buffer)?,
+            self.max_wrong_guesses.write(buffer)?,
+            self.time_limit.write(buffer)?,
+            self.turn_limit.write(buffer)?,
+            self.hint_limit.write(buffer)?,
+            self.min_word_length.write(buffer)?,
+            self.max_word_length.write(buffer)?,
+            self.rounds.write(buffer)?,
+        ];
+
+        Ok(n.iter().sum())
+    }
+}
+
+impl ReadBytes for MatchOptions {
+    fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
+        Some(Self {
+            ruleset: String::read(buffer)?,
+            language: String::read(buffer)?,
+            max_wrong_guesses: <Option<u16>>::read(buffer)?,
+            time_limit: <Option<u16>>::read(buffer)?,
+            turn_limit: <Option<u16>>::read(buffer)?,
+            hint_limit: <Option<u16>>::read(buffer)?,
+            min_word_length: <Option<u16>>::read(buffer)?,
+            max_word_length: <Option<u16>>::read(buffer)?,
+            rounds: u16::read(buffer)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut options = MatchOptions::default();
+        for option in ["rules=wordle", "guesses=6", "length=4-8", "time=120"] {
+            options.apply(option).unwrap();
+        }
+
+        assert_eq!(options.ruleset, "wordle");
+        assert_eq!(options.max_wrong_guesses, Some(6));
+        assert_eq!((options.min_word_length, options.max_word_length), (Some(4), Some(8)));
+        assert!(options.validate().is_ok());
+        assert!(!options.allows_length(3));
+
+        assert!(options.apply("guesses=many").is_err());
+        options.apply("length=9-").unwrap();
+        assert_eq!((options.min_word_length, options.max_word_length), (Some(9), None));
+        options.apply("lang=xx").unwrap();
+        assert!(options.validate().is_err());
+        options.apply("lang=fr").unwrap();
+        assert!(options.validate().is_ok());
+        options.apply("rules=chess").unwrap();
+        assert!(options.validate().is_err());
+
+        // a client which sends no ruleset gets the default one
+        options.ruleset.clear();
+        assert!(options.normalize().is_ok());
+        assert_eq!(options.ruleset, MatchOptions::default().ruleset);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut options = MatchOptions::default();
+        options.apply("hints=3").unwrap();
+
+        let mut buffer = Vec::new();
+        options.write(&mut buffer).unwrap();
+
+        assert_eq!(MatchOptions::read(&mut buffer.iter()), Some(options));
+    }
+}
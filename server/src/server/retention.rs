@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+
+use super::{GameChange, History, Server, ServerState};
+
+/// How many finished games are kept in memory and for how long.
+/// Games past either limit are written to the archive, or dropped without one, and new subscribers only get the games still kept.
+#[derive(Default)]
+pub struct Retention {
+    pub(super) max_games: Option<usize>,
+    /// Seconds after the end of a game.
+    pub(super) max_age: Option<u64>,
+    /// Finished games appended as JSON lines, like subscribers are sent them.
+    pub(super) archive: Option<History>,
+    /// Finished games in memory by the time they ended and their id, the oldest first.
+    pub(super) finished: BTreeSet<(u64, u32)>,
+}
+
+impl Retention {
+    /// Whether the oldest finished game is past one of the limits, so [`ServerState::prune_games`] has something to remove.
+    /// Games which ended within the rematch grace period are kept, a rematch still needs the last game of its match.
+    pub(super) fn is_due(&self, now: u64) -> bool {
+        let Some((ended, _)) = self.finished.first() else {
+            return false
+        };
+
+        let age = now.saturating_sub(*ended);
+        age >= Server::REMATCH_GRACE && (self.max_games.is_some_and(|max| self.finished.len() > max) || self.max_age.is_some_and(|max| age > max))
+    }
+}
+
+impl ServerState {
+    /// Forget games which are no longer active, the finished ones count against the limits of the retention from now on.
+    pub(super) fn retire_games(&mut self, ids: impl IntoIterator<Item = u32>) {
+        for id in ids {
+            self.active_games.remove(&id);
+            if let Some(game) = self.games.get(&id) {
+                let game = game.read().unwrap();
+                if game.is_finished() {
+                    self.retention.finished.insert((game.timestamp, id));
+                }
+            }
+        }
+    }
+
+    /// Remove the oldest finished games while they are past the limits of the retention, returns their ids.
+    pub(super) fn prune_games(&mut self, now: u64) -> Vec<u32> {
+        let mut pruned = Vec::new();
+
+        while self.retention.is_due(now) {
+            let Some((_, id)) = self.retention.finished.pop_first() else {
+                break
+            };
+            let Some(game) = self.games.remove(&id) else {
+                continue
+            };
+
+            let mut game = game.write().unwrap();
+            let archived = self.retention.archive.as_ref().is_some_and(|archive| match archive.append(&*game) {
+                Ok(()) => true,
+                Err(err) => {
+                    eprintln!("Failed to archive game {}: {:?}", id, err);
+                    false
+                },
+            });
+
+            // the history has to know, or the game would be put back on the next start
+            game.record(GameChange::Pruned { archived });
+            pruned.push(id);
+        }
+
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EndReason, Equivalence, MatchOptions};
+
+    #[test]
+    fn test_prune_games() {
+        let path = std::env::temp_dir().join(format!("archive-test-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut state = ServerState::new();
+        state.retention = Retention {
+            max_games: Some(2),
+            max_age: Some(1000),
+            archive: Some(History::open(path).unwrap()),
+            ..Default::default()
+        };
+
+        let now = 10_000;
+        for (ended, finish) in [(now - 2000, true), (now - 300, true), (now - 200, true), (now - 100, true), (now - 10, true), (0, false)] {
+            let game = state.create_game(1, 2, MatchOptions::default(), Equivalence::default());
+            let mut game = game.write().unwrap();
+            if finish {
+                game.finish(None, EndReason::AbortedByAdmin).unwrap();
+                game.timestamp = ended;
+            }
+        }
+        // the timers retire the games once they are over
+        state.retire_games(1..=5);
+
+        let mut pruned = state.prune_games(now);
+        pruned.sort();
+        assert_eq!(pruned, [1, 2, 3]);
+
+        let mut kept = state.games.keys().copied().collect::<Vec<_>>();
+        kept.sort();
+        assert_eq!(kept, [4, 5, 6]);
+
+        let archive = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(archive.lines().count(), 3);
+        assert!(state.prune_games(now).is_empty());
+    }
+}
@@ -0,0 +1,490 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use crate::{AppResult, Command};
+
+use super::{get_timestamp, AMPlayer, ARWServerState, EndReason, Equivalence, Game, GameError, MatchOptions, Server, ServerConfig, ServerState};
+
+/// A room where one hinter sets a word for any number of guessers who race to find it.
+/// Every guesser plays their own [`Game`] against the hinter, all games of a round share the word and the hints.
+pub struct Room {
+    pub(super) id: u32,
+    pub(super) hinter: u32,
+    /// Guessers in the order they joined.
+    pub(super) guessers: Vec<u32>,
+    pub(super) options: MatchOptions,
+    pub(super) equivalence: Equivalence,
+    /// The word of the current round, `None` between rounds.
+    word: Option<String>,
+    hints: Vec<String>,
+    /// The game of each guesser in the current round.
+    games: HashMap<u32, u32>,
+    /// Games of the current round which are over.
+    finished: HashSet<u32>,
+    /// Guessers who found the word this round, fastest first.
+    ranking: Vec<u32>,
+    /// When the hinter was asked for the next word, `None` if they have not been.
+    word_requested: Option<u64>,
+}
+
+impl Room {
+    pub(super) fn new(id: u32, hinter: u32, options: MatchOptions, equivalence: Equivalence) -> Self {
+        Self {
+            id,
+            hinter,
+            guessers: Vec::new(),
+            options,
+            equivalence,
+            word: None,
+            hints: Vec::new(),
+            games: HashMap::new(),
+            finished: HashSet::new(),
+            ranking: Vec::new(),
+            word_requested: None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.word.is_some()
+    }
+
+    /// Whether the hinter was asked for a word and let the turn limit pass without setting one, like the hinter of a game.
+    fn word_overdue(&self, now: u64) -> bool {
+        let Some((requested, limit)) = self.word_requested.zip(self.options.turn_limit) else {
+            return false
+        };
+
+        !self.is_playing() && now >= requested + limit as u64
+    }
+
+    /// The hinter followed by every guesser.
+    fn members(&self) -> Vec<u32> {
+        std::iter::once(self.hinter).chain(self.guessers.iter().copied()).collect()
+    }
+
+    /// Count a finished game of the current round, returns whether the round is over.
+    fn record(&mut self, game: &Game) -> bool {
+        if !self.is_playing() || self.games.get(&game.guesser) != Some(&game.id) || !self.finished.insert(game.id) {
+            return false
+        }
+
+        if game.end_reason == Some(EndReason::Guessed) {
+            self.ranking.push(game.guesser);
+        }
+
+        self.finished.len() == self.games.len()
+    }
+
+    /// Clear the current round, returns the guessers who found the word, fastest first.
+    fn end_round(&mut self) -> Vec<u32> {
+        self.word = None;
+        self.hints.clear();
+        self.games.clear();
+        self.finished.clear();
+        std::mem::take(&mut self.ranking)
+    }
+}
+
+impl Server {
+    /// Open a room with the player as its hinter, the options apply to the game of every guesser.
+    pub(super) fn create_room(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, mut options: MatchOptions) -> AppResult<()> {
+        if player.read().unwrap().is_busy() {
+            return Self::send_error(player, "Cannot open a room while playing")
+        }
+
+        if let Err(err) = options.normalize() {
+            return Self::send_error(player, err)
+        }
+
+        let mut state = state.write().unwrap();
+        let mut player = player.write().unwrap();
+
+        let room_id = state.create_room(player.id, config.apply_defaults(options), config.equivalence);
+        state.matchmaker.leave(player.id);
+        player.in_room = Some(room_id);
+
+        Self::send(&mut player.connection, Command::RoomJoined(room_id.to_string()))
+    }
+
+    /// Join a room as a guesser, a running round is joined with the word and all hints given so far.
+    pub(super) fn join_room(player: &AMPlayer, state: &ARWServerState, id: String) -> AppResult<()> {
+        let Ok(room_id) = id.parse() else {
+            return Self::send_error(player, "Invalid room id")
+        };
+
+        if player.read().unwrap().is_busy() {
+            return Self::send_error(player, "Cannot join a room while playing")
+        }
+
+        let mut state = state.write().unwrap();
+        let self_id = player.read().unwrap().id;
+
+        let Some(room) = state.rooms.get_mut(&room_id) else {
+            drop(state);
+            return Self::send_error(player, "Room not found")
+        };
+
+        room.guessers.push(self_id);
+        let playing = room.is_playing();
+        let request_word = !playing && room.word_requested.is_none();
+        if request_word {
+            room.word_requested = Some(get_timestamp());
+        }
+
+        state.matchmaker.leave(self_id);
+        {
+            let mut player = player.write().unwrap();
+            player.in_room = Some(room_id);
+            Self::send(&mut player.connection, Command::RoomJoined(room_id.to_string()))?;
+        }
+
+        Self::send_room_members(&state, room_id);
+
+        if playing {
+            return Self::add_room_game(&mut state, room_id, self_id)
+        }
+
+        if request_word {
+            Self::request_room_word(&state, room_id);
+        }
+
+        Ok(())
+    }
+
+    /// Ask the hinter for the next word and tell them how long they have to set it.
+    fn request_room_word(state: &ServerState, room_id: u32) {
+        let Some(room) = state.rooms.get(&room_id) else {
+            return
+        };
+
+        Self::notify(state, room.hinter, Command::RequestMatchGuess);
+        if let Some(limit) = room.options.turn_limit {
+            Self::notify(state, room.hinter, Command::TimeLeft(limit));
+        }
+    }
+
+    /// Close every room whose hinter did not set a word in time, called by the timers every second.
+    /// The guessers are sent back to the menu like when the hinter leaves.
+    pub(super) fn close_idle_rooms(state: &ARWServerState, now: u64) {
+        let idle = state.read().unwrap().rooms.values()
+            .filter(|room| room.word_overdue(now))
+            .map(|room| room.id)
+            .collect::<Vec<_>>();
+        if idle.is_empty() {
+            return
+        }
+
+        let mut state = state.write().unwrap();
+        for room_id in idle {
+            let Some(room) = state.rooms.remove(&room_id) else {
+                continue
+            };
+
+            if let Some(hinter) = state.players.get(&room.hinter) {
+                let mut hinter = hinter.write().unwrap();
+                hinter.in_room = None;
+                if let Err(err) = Self::send(&mut hinter.connection, Command::Error("No word was set in time, the room is closed".to_string())) {
+                    eprintln!("Failed to notify player {}: {:?}", room.hinter, err);
+                }
+            }
+            Self::close_room(&state, room);
+        }
+    }
+
+    /// Leave the current room, a guesser forfeits their game of the round and a leaving hinter closes the room.
+    pub(super) fn leave_room(player: &AMPlayer, state: &ARWServerState, reason: EndReason) -> AppResult<()> {
+        let (self_id, in_room) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_room)
+        };
+
+        let Some(room_id) = in_room else {
+            return Ok(())
+        };
+
+        {
+            let mut state = state.write().unwrap();
+            player.write().unwrap().in_room = None;
+
+            let Some(room) = state.rooms.get_mut(&room_id) else {
+                return Ok(())
+            };
+
+            if room.hinter == self_id {
+                let room = state.rooms.remove(&room_id).expect("room exists");
+                Self::close_room(&state, room);
+                return Ok(())
+            }
+
+            room.guessers.retain(|id| *id != self_id);
+            Self::send_room_members(&state, room_id);
+        }
+
+        Self::forfeit(player, state, Command::Surrender, reason)
+    }
+
+    /// End the running games of a room whose hinter left, every guesser wins their game and is sent back to the menu.
+    fn close_room(state: &ServerState, room: Room) {
+        for (guesser_id, game_id) in &room.games {
+            let Some(game) = state.games.get(game_id) else {
+                continue
+            };
+
+            let mut game = game.write().unwrap();
+            if game.is_finished() {
+                continue
+            }
+            if let Err(err) = Self::end_game(state, &mut game, Some(*guesser_id), EndReason::Disconnected) {
+                eprintln!("Failed to end game {}: {:?}", game.id, err);
+            }
+        }
+
+        for guesser_id in room.guessers {
+            let Some(guesser) = state.players.get(&guesser_id) else {
+                continue
+            };
+
+            let mut guesser = guesser.write().unwrap();
+            guesser.in_room = None;
+            if let Err(err) = Self::send(&mut guesser.connection, Command::RoomClosed) {
+                eprintln!("Failed to notify player {}: {:?}", guesser_id, err);
+            }
+        }
+    }
+
+    /// Send the ids of all open rooms.
+    pub(super) fn list_rooms(player: &AMPlayer, state: &ARWServerState) -> AppResult<()> {
+        let mut rooms = state.read().unwrap().rooms.keys().copied().collect::<Vec<_>>();
+        rooms.sort();
+
+        let rooms = rooms.iter().map(|id| id.to_string()).collect();
+        Self::send(&mut player.write().unwrap().connection, Command::Rooms(rooms))
+    }
+
+    /// Start a round with the word from the hinter, every guesser in the room gets a game.
+    pub(super) fn set_room_word(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, word: String) -> AppResult<()> {
+        let (self_id, in_room) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_room)
+        };
+
+        let mut state = state.write().unwrap();
+        let Some(room) = in_room.and_then(|room_id| state.rooms.get_mut(&room_id)) else {
+            drop(state);
+            return Self::send_error(player, "Not in a room")
+        };
+
+        if room.hinter != self_id {
+            return Err(GameError::NotHinter.into())
+        }
+        if room.is_playing() {
+            return Err(GameError::WordAlreadySet.into())
+        }
+        if room.guessers.is_empty() {
+            room.word_requested = None;
+            drop(state);
+            return Self::send_error(player, "Nobody joined the room yet")
+        }
+
+        let language = room.options.language.clone();
+        if !Self::check_word(player, config, &language, &word)? {
+            return Ok(())
+        }
+        let room = in_room.and_then(|room_id| state.rooms.get_mut(&room_id)).expect("room exists");
+
+        // every game gets the same word, so it is checked once before any of them is created
+        let word = Game::check_word(&room.options, &word)?;
+        let room_id = room.id;
+        let guessers = room.guessers.clone();
+        room.word = Some(word);
+
+        for guesser_id in guessers {
+            if let Err(err) = Self::add_room_game(&mut state, room_id, guesser_id) {
+                eprintln!("Failed to start the game of player {}: {:?}", guesser_id, err);
+            }
+        }
+
+        let room = state.rooms.get_mut(&room_id).expect("room exists");
+        room.word_requested = None;
+        Ok(())
+    }
+
+    /// Create the game of a guesser for the running round and send them the word and all hints so far.
+    fn add_room_game(state: &mut ServerState, room_id: u32, guesser_id: u32) -> AppResult<()> {
+        let room = &state.rooms[&room_id];
+        if room.games.contains_key(&guesser_id) {
+            // rejoining during the same round has to wait for the next one
+            return Ok(())
+        }
+
+        let hinter = room.hinter;
+        let word = room.word.clone().unwrap_or_default();
+        let hints = room.hints.clone();
+        let mut game = state.new_game(hinter, guesser_id, room.options.clone(), room.equivalence);
+        game.room_id = Some(room_id);
+        let game = state.add_game(game);
+
+        let (game_id, mask) = {
+            let mut game = game.write().unwrap();
+            let result = game.set_word(hinter, word).and_then(|()| hints.iter().try_for_each(|hint| game.add_hint(hinter, hint.clone())));
+            if let Err(err) = result {
+                state.games.remove(&game.id);
+                return Err(err.into())
+            }
+            (game.id, game.mask())
+        };
+
+        state.rooms.get_mut(&room_id).expect("room exists").games.insert(guesser_id, game_id);
+        Self::send_names(state, guesser_id, [hinter])?;
+
+        let Some(guesser) = state.players.get(&guesser_id) else {
+            return Ok(())
+        };
+        let mut guesser = guesser.write().unwrap();
+        guesser.in_game = Some(game_id);
+
+        Self::send(&mut guesser.connection, Command::PlayerJoined(hinter.to_string()))?;
+        Self::send(&mut guesser.connection, Command::SetGuess(mask))?;
+        for hint in hints {
+            Self::send(&mut guesser.connection, Command::Hint(hint))?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a hint from the hinter to every guesser who is still playing the round.
+    pub(super) fn room_hint(player: &AMPlayer, state: &ARWServerState, hint: String) -> AppResult<()> {
+        let (self_id, in_room) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_room)
+        };
+
+        let mut state = state.write().unwrap();
+        let Some(room) = in_room.and_then(|room_id| state.rooms.get(&room_id)) else {
+            drop(state);
+            return Self::send_error(player, "Not in a room")
+        };
+
+        if room.hinter != self_id {
+            return Err(GameError::NotHinter.into())
+        }
+        if !room.is_playing() {
+            return Err(GameError::WordNotSet.into())
+        }
+
+        let room_id = room.id;
+        let mut guessers = Vec::new();
+        for (guesser_id, game_id) in &room.games {
+            let Some(game) = state.games.get(game_id) else {
+                continue
+            };
+
+            let mut game = game.write().unwrap();
+            if game.is_finished() {
+                continue
+            }
+
+            // the games share their hints, so the first one decides for all of them
+            game.add_hint(self_id, hint.clone())?;
+            guessers.push(*guesser_id);
+        }
+
+        state.rooms.get_mut(&room_id).expect("room exists").hints.push(hint.clone());
+
+        for guesser_id in guessers {
+            Self::notify(&state, guesser_id, Command::Hint(hint.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Count a finished game for its room, once every guesser is done the ranking is sent to all members and the hinter is asked for the next word.
+    pub(super) fn finish_room_game(state: &mut ServerState, room_id: u32, game: &Game) -> AppResult<()> {
+        let Some(room) = state.rooms.get_mut(&room_id) else {
+            return Ok(())
+        };
+
+        if !room.record(game) {
+            return Ok(())
+        }
+
+        let ranking = room.end_round().iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let request_word = !room.guessers.is_empty();
+        room.word_requested = request_word.then(get_timestamp);
+        let members = room.members();
+
+        for member in members {
+            Self::notify(state, member, Command::RoomRanking(ranking.clone()));
+        }
+
+        if request_word {
+            Self::request_room_word(state, room_id);
+        }
+
+        Ok(())
+    }
+
+    /// Tell every member of the room who is guessing, together with their names.
+    fn send_room_members(state: &ServerState, room_id: u32) {
+        let Some(room) = state.rooms.get(&room_id) else {
+            return
+        };
+
+        let guessers = room.guessers.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        for member in room.members() {
+            let result = Self::send_names(state, member, room.guessers.iter().copied())
+                .and_then(|()| Self::send_to(state, member, Command::RoomMembers(guessers.clone())));
+            if let Err(err) = result {
+                eprintln!("Failed to notify player {}: {:?}", member, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round() {
+        let mut room = Room::new(1, 1, MatchOptions::default(), Equivalence::default());
+        room.guessers = vec![2, 3];
+        room.word = Some("Kaffi".to_string());
+        room.games = HashMap::from([(2, 10), (3, 11)]);
+
+        let mut first = Game::new(10, 1, 2, MatchOptions::default(), Equivalence::default());
+        let mut second = Game::new(11, 1, 3, MatchOptions::default(), Equivalence::default());
+        second.finish(Some(3), EndReason::Guessed).unwrap();
+        first.finish(Some(1), EndReason::Surrendered).unwrap();
+
+        assert!(!room.record(&second));
+        // every game is only counted once
+        assert!(!room.record(&second));
+        assert!(room.record(&first));
+
+        assert_eq!(room.end_round(), vec![3]);
+        assert!(!room.is_playing());
+        assert!(!room.record(&first));
+    }
+
+    #[test]
+    fn test_word_overdue() {
+        let options = MatchOptions {
+            turn_limit: Some(60),
+            ..Default::default()
+        };
+        let mut room = Room::new(1, 1, options, Equivalence::default());
+        assert!(!room.word_overdue(1000));
+
+        room.word_requested = Some(100);
+        assert!(!room.word_overdue(159));
+        assert!(room.word_overdue(160));
+
+        // the deadline is over once the word is set
+        room.word = Some("Kaffi".to_string());
+        assert!(!room.word_overdue(160));
+
+        room.options.turn_limit = None;
+        room.word = None;
+        assert!(!room.word_overdue(1000));
+    }
+}
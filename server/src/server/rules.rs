@@ -0,0 +1,200 @@
+use crate::Command;
+
+use super::{graphemes, letter_feedback, EndReason, Game, GameError, GameResult};
+
+/// Every ruleset a match can be played with, new variants only have to be added here.
+static RULESETS: [&dyn GameRules; 3] = [&Freeform, &Reveal, &Wordle];
+
+/// Look up a ruleset by the name used in [`Command::RequestMatch`].
+pub fn ruleset(name: &str) -> Option<&'static dyn GameRules> {
+    RULESETS.iter().copied().find(|rules| rules.name() == name)
+}
+
+/// Names of all available rulesets.
+pub fn ruleset_names() -> Vec<&'static str> {
+    RULESETS.iter().map(|rules| rules.name()).collect()
+}
+
+/// What happened after a guess was recorded.
+pub struct GuessOutcome {
+    pub correct: bool,
+    /// The guess brought the guesser no closer to the word, counted against [`MatchOptions::max_wrong_guesses`](super::MatchOptions::max_wrong_guesses).
+    pub wrong: bool,
+    /// Additional information for the guesser, like the uncovered part of the word.
+    pub response: Option<Command>,
+}
+
+/// The game logic of a variant, the default methods implement the freeform game.
+pub trait GameRules: Send + Sync {
+    /// Name used to select the ruleset and shown in the game JSON.
+    fn name(&self) -> &'static str;
+
+    fn validate_word(&self, word: &str) -> GameResult<()> {
+        if word.trim().is_empty() {
+            return Err(GameError::Rejected("Word cannot be empty"))
+        }
+
+        Ok(())
+    }
+
+    fn validate_hint(&self, _game: &Game, hint: &str) -> GameResult<()> {
+        if hint.trim().is_empty() {
+            return Err(GameError::Rejected("Hint cannot be empty"))
+        }
+
+        Ok(())
+    }
+
+    /// Prepare the game once the word is set, by default nothing is revealed.
+    fn start(&self, game: &mut Game) {
+        game.revealed = vec![false; graphemes(game.word()).len()];
+    }
+
+    /// Check the guess against the word, the guess is already recorded in [`Game::guesses`].
+    fn evaluate_guess(&self, game: &mut Game, guess: &str) -> GuessOutcome {
+        let correct = game.equivalence.matches(game.word(), guess);
+
+        GuessOutcome {
+            correct,
+            wrong: !correct,
+            response: None,
+        }
+    }
+
+    /// Whether the opponents of the guesser are sent the guess itself, otherwise they only get the [`GuessOutcome::response`] if there is one.
+    fn forwards_guesses(&self) -> bool {
+        true
+    }
+
+    /// Returns the winner and the reason if the guess ended the game, the hinter wins once the guesser is out of guesses.
+    fn decide_end(&self, game: &Game, outcome: &GuessOutcome) -> Option<(Option<u32>, EndReason)> {
+        if outcome.correct {
+            Some((Some(game.guesser), EndReason::Guessed))
+        } else if game.is_out_of_guesses() {
+            Some((Some(game.hinter), EndReason::OutOfGuesses))
+        } else {
+            None
+        }
+    }
+
+    /// Points awarded to the winner of a finished game.
+    fn score(&self, game: &Game) -> u32 {
+        match game.winner {
+            Some(winner) if winner == game.guesser => 10u32.saturating_sub((game.guesses.len() as u32).saturating_sub(1)).max(1),
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+}
+
+/// Only a guess of the whole word counts.
+pub struct Freeform;
+
+impl GameRules for Freeform {
+    fn name(&self) -> &'static str {
+        "freeform"
+    }
+}
+
+/// Hangman style, single letter guesses uncover every matching position.
+pub struct Reveal;
+
+impl GameRules for Reveal {
+    fn name(&self) -> &'static str {
+        "reveal"
+    }
+
+    fn validate_word(&self, word: &str) -> GameResult<()> {
+        if !word.chars().any(char::is_alphabetic) {
+            return Err(GameError::Rejected("Word has to contain a letter"))
+        }
+
+        Ok(())
+    }
+
+    /// Only letters have to be guessed, spaces and dashes are shown right away.
+    fn start(&self, game: &mut Game) {
+        game.revealed = graphemes(game.word()).iter()
+            .map(|g| !g.chars().any(char::is_alphabetic))
+            .collect();
+    }
+
+    fn evaluate_guess(&self, game: &mut Game, guess: &str) -> GuessOutcome {
+        let mut correct = game.equivalence.matches(game.word(), guess);
+        let hidden = game.revealed.iter().filter(|revealed| !**revealed).count();
+
+        if graphemes(guess).len() == 1 && !game.equivalence.fold(guess).is_empty() {
+            let word = game.word().to_string();
+            for (g, revealed) in graphemes(&word).into_iter().zip(game.revealed.iter_mut()) {
+                if game.equivalence.matches(g, guess) {
+                    *revealed = true;
+                }
+            }
+
+            correct |= game.revealed.iter().all(|revealed| *revealed);
+        }
+
+        let mask = game.mask();
+        game.reveals.push(mask.clone());
+
+        GuessOutcome {
+            correct,
+            wrong: !correct && game.revealed.iter().filter(|revealed| !**revealed).count() == hidden,
+            response: Some(Command::Reveal(mask)),
+        }
+    }
+
+    /// One point for every letter which was still hidden when the word was solved.
+    fn score(&self, game: &Game) -> u32 {
+        if game.winner != Some(game.guesser) {
+            return Freeform.score(game)
+        }
+
+        let hidden = game.reveals.iter().rev().nth(1)
+            .map(|mask| mask.matches('_').count())
+            .unwrap_or(game.revealed.len());
+        hidden.max(1) as u32
+    }
+}
+
+/// Guesses as long as the word are answered with per letter feedback.
+pub struct Wordle;
+
+impl GameRules for Wordle {
+    fn name(&self) -> &'static str {
+        "wordle"
+    }
+
+    fn validate_word(&self, word: &str) -> GameResult<()> {
+        if word.is_empty() || word.chars().any(char::is_whitespace) {
+            return Err(GameError::Rejected("Word has to be a single word"))
+        }
+
+        Ok(())
+    }
+
+    fn evaluate_guess(&self, game: &mut Game, guess: &str) -> GuessOutcome {
+        let correct = game.equivalence.matches(game.word(), guess);
+        let feedback = letter_feedback(game.word(), guess, &game.equivalence);
+        game.feedback.push(feedback.clone());
+
+        GuessOutcome {
+            correct,
+            wrong: !correct,
+            response: feedback.map(|feedback| Command::Feedback(guess.to_string(), feedback)),
+        }
+    }
+
+    /// The hinter follows the game through the feedback.
+    fn forwards_guesses(&self) -> bool {
+        false
+    }
+
+    /// Solving in fewer guesses gives more points, like the original six attempts.
+    fn score(&self, game: &Game) -> u32 {
+        match game.winner {
+            Some(winner) if winner == game.guesser => 7u32.saturating_sub(game.guesses.len() as u32).max(1),
+            _ => Freeform.score(game),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::{AppError, AppResult, Command, GameError};
+
+use super::{graphemes, AMPlayer, ARWServerState, Game, GamePhase, MatchOptions, Server, ServerConfig, ServerState};
+
+/// The hinter of solo games, no player ever gets this id.
+pub const SERVER_ID: u32 = 0;
+
+/// The hint the server gives after `given` hints, `None` once only one letter would be left to find.
+/// The first and the last letter come first, then the letters in between from the left.
+pub(super) fn solo_hint(word: &str, given: usize) -> Option<String> {
+    let letters = graphemes(word);
+    let last = letters.len().checked_sub(1)?;
+    if given > 0 && given >= last {
+        return None
+    }
+
+    let hint = match given {
+        0 => format!("The word starts with {}", letters[0]),
+        1 => format!("The word ends with {}", letters[last]),
+        n => format!("Letter {} is {}", n, letters[n - 1]),
+    };
+
+    Some(hint)
+}
+
+impl Server {
+    /// Seconds between the hints of a solo game, a requested hint pushes the next one back.
+    const SOLO_HINT_INTERVAL: u64 = 30;
+
+    /// Start a game in which the server picks the word from its word list and the player guesses it.
+    /// The game runs like any other, the player is sent [`Command::PlayerJoined`], [`Command::SetGuess`] and the first hint.
+    pub(super) fn start_solo(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, mut options: MatchOptions) -> AppResult<()> {
+        if player.read().unwrap().is_busy() {
+            return Self::send_error(player, "Cannot practice while playing")
+        }
+
+        if let Err(err) = options.normalize() {
+            return Self::send_error(player, err)
+        }
+        options.rounds = 1;
+
+        let options = config.apply_defaults(options);
+        let Some(word) = config.pick_word(&options) else {
+            return Self::send_error(player, "No word in the word list fits the options")
+        };
+
+        let mut state = state.write().unwrap();
+        let self_id = player.read().unwrap().id;
+        state.matchmaker.leave(self_id);
+
+        let game = state.create_game(SERVER_ID, self_id, options, config.equivalence);
+        let mut game = game.write().unwrap();
+        game.set_word(SERVER_ID, word)?;
+        state.solo_games.insert(game.id);
+
+        {
+            let mut player = player.write().unwrap();
+            player.in_game = Some(game.id);
+            Self::send(&mut player.connection, Command::PlayerJoined(SERVER_ID.to_string()))?;
+            Self::send(&mut player.connection, Command::SetGuess(game.mask()))?;
+        }
+
+        // without any hints allowed the player only gets the mask
+        match Self::give_solo_hint(&state, &mut game) {
+            Err(AppError::Game(_)) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Give the next hint of the solo game the player is in.
+    pub(super) fn request_hint(player: &AMPlayer, state: &ARWServerState) -> AppResult<()> {
+        let Some(game) = Self::get_game(player, state)? else {
+            return Ok(())
+        };
+
+        let state = state.read().unwrap();
+        let mut game = game.write().unwrap();
+        if game.hinter != SERVER_ID {
+            return Err(GameError::Rejected("Only the server gives hints on request").into())
+        }
+
+        Self::give_solo_hint(&state, &mut game)
+    }
+
+    /// Give the next hints of solo games which waited long enough for one, returns the ids of the solo games which are over.
+    pub(super) fn schedule_solo_hints(state: &ServerState, now: u64) -> Vec<u32> {
+        let mut finished = Vec::new();
+
+        for game_id in &state.solo_games {
+            let Some(game) = state.games.get(game_id) else {
+                finished.push(*game_id);
+                continue
+            };
+
+            let mut game = game.write().unwrap();
+            if game.phase != GamePhase::InProgress {
+                finished.push(game.id);
+                continue
+            }
+
+            let due = game.started + Self::SOLO_HINT_INTERVAL * game.hints.len() as u64;
+            if now < due {
+                continue
+            }
+
+            // running out of hints is only an error when the player asks for one
+            if let Err(err) = Self::give_solo_hint(state, &mut game) {
+                if !matches!(err, AppError::Game(_)) {
+                    eprintln!("Failed to give hint in game {}: {:?}", game.id, err);
+                }
+            }
+        }
+
+        finished
+    }
+
+    fn give_solo_hint(state: &ServerState, game: &mut Game) -> AppResult<()> {
+        let Some(hint) = solo_hint(game.word(), game.hints.len()) else {
+            return Err(GameError::Rejected("No hints left").into())
+        };
+
+        game.add_hint(SERVER_ID, hint.clone())?;
+        Self::send_to(state, game.guesser, Command::Hint(hint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solo_hint() {
+        let hints = (0..).map_while(|given| solo_hint("Fënster", given)).collect::<Vec<_>>();
+        assert_eq!(hints, [
+            "The word starts with F",
+            "The word ends with r",
+            "Letter 2 is ë",
+            "Letter 3 is n",
+            "Letter 4 is s",
+            "Letter 5 is t",
+        ]);
+
+        assert_eq!(solo_hint("Kaz", 2), None);
+        assert_eq!(solo_hint("a", 0).as_deref(), Some("The word starts with a"));
+        assert_eq!(solo_hint("a", 1), None);
+    }
+}
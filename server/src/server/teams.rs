@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AppResult, Command};
+
+use super::{AMPlayer, ARWServerState, MatchOptions, Server, ServerConfig, ServerState};
+
+/// One side of a team game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Team {
+    pub(super) members: Vec<u32>,
+    /// Points of the team in its match so far, including the game it belongs to.
+    pub(super) score: u32,
+}
+
+impl Team {
+    pub(super) fn new(members: Vec<u32>, score: u32) -> Self {
+        Self {
+            members,
+            score,
+        }
+    }
+}
+
+/// Players picking their team before a team match, stored in [`ServerState::lobbies`].
+pub struct TeamLobby {
+    pub(super) id: u32,
+    /// The player who starts the match, the first one left if they leave.
+    pub(super) owner: u32,
+    pub(super) teams: [Vec<u32>; 2],
+    pub(super) options: MatchOptions,
+}
+
+impl TeamLobby {
+    pub(super) fn new(id: u32, owner: u32, options: MatchOptions) -> Self {
+        Self {
+            id,
+            owner,
+            teams: [vec![owner], Vec::new()],
+            options,
+        }
+    }
+
+    fn members(&self) -> Vec<u32> {
+        self.teams.iter().flatten().copied().collect()
+    }
+
+    /// Put the player into a team, moving them out of the other one.
+    fn join(&mut self, player_id: u32, team: usize) {
+        self.remove(player_id);
+        self.teams[team].push(player_id);
+    }
+
+    /// Remove the player, returns whether the lobby is empty afterwards.
+    fn leave(&mut self, player_id: u32) -> bool {
+        self.remove(player_id);
+
+        match self.members().first() {
+            Some(first) => {
+                if self.owner == player_id {
+                    self.owner = *first;
+                }
+                false
+            },
+            None => true,
+        }
+    }
+
+    fn remove(&mut self, player_id: u32) {
+        for team in &mut self.teams {
+            team.retain(|id| *id != player_id);
+        }
+    }
+}
+
+impl Server {
+    /// Open a team lobby with the player in the first team, the match is played with the given options.
+    pub(super) fn create_teams(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>, mut options: MatchOptions) -> AppResult<()> {
+        if player.read().unwrap().is_busy() {
+            return Self::send_error(player, "Cannot form teams while playing")
+        }
+
+        if let Err(err) = options.normalize() {
+            return Self::send_error(player, err)
+        }
+
+        // both teams need a turn at hinting
+        options.rounds = options.rounds.max(2);
+
+        let mut state = state.write().unwrap();
+        let lobby_id = {
+            let mut player = player.write().unwrap();
+            let lobby_id = state.create_lobby(player.id, config.apply_defaults(options));
+            state.matchmaker.leave(player.id);
+            player.in_lobby = Some(lobby_id);
+            lobby_id
+        };
+
+        Self::send_team_members(&state, lobby_id);
+        Ok(())
+    }
+
+    /// Join or switch to one of the two teams of a lobby, `team` is 1 or 2.
+    pub(super) fn join_team(player: &AMPlayer, state: &ARWServerState, id: String, team: u8) -> AppResult<()> {
+        let Ok(lobby_id) = id.parse() else {
+            return Self::send_error(player, "Invalid lobby id")
+        };
+
+        if !(1..=2).contains(&team) {
+            return Self::send_error(player, "There are only teams 1 and 2")
+        }
+
+        let (self_id, in_game, in_room, in_lobby) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_game, player.in_room, player.in_lobby)
+        };
+
+        if in_game.is_some() || in_room.is_some() || in_lobby.is_some_and(|current| current != lobby_id) {
+            return Self::send_error(player, "Cannot join a team while playing")
+        }
+
+        let mut state = state.write().unwrap();
+        let Some(lobby) = state.lobbies.get_mut(&lobby_id) else {
+            drop(state);
+            return Self::send_error(player, "Lobby not found")
+        };
+
+        lobby.join(self_id, team as usize - 1);
+        state.matchmaker.leave(self_id);
+        player.write().unwrap().in_lobby = Some(lobby_id);
+
+        Self::send_team_members(&state, lobby_id);
+        Ok(())
+    }
+
+    /// Leave the current lobby, the remaining members are told the new teams.
+    pub(super) fn leave_teams(player: &AMPlayer, state: &ARWServerState) -> AppResult<()> {
+        let (self_id, in_lobby) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_lobby)
+        };
+
+        let Some(lobby_id) = in_lobby else {
+            return Ok(())
+        };
+
+        let mut state = state.write().unwrap();
+        player.write().unwrap().in_lobby = None;
+
+        let Some(lobby) = state.lobbies.get_mut(&lobby_id) else {
+            return Ok(())
+        };
+
+        if lobby.leave(self_id) {
+            state.lobbies.remove(&lobby_id);
+        } else {
+            Self::send_team_members(&state, lobby_id);
+        }
+
+        Ok(())
+    }
+
+    /// Start the match of a lobby, only its owner can and both teams need at least one player.
+    /// The first team hints first, the teams swap roles every round.
+    pub(super) fn start_teams(player: &AMPlayer, state: &ARWServerState, config: &Arc<ServerConfig>) -> AppResult<()> {
+        let (self_id, in_lobby) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_lobby)
+        };
+
+        let mut state = state.write().unwrap();
+        let Some(lobby) = in_lobby.and_then(|lobby_id| state.lobbies.get(&lobby_id)) else {
+            drop(state);
+            return Self::send_error(player, "Not in a lobby")
+        };
+
+        if lobby.owner != self_id {
+            drop(state);
+            return Self::send_error(player, "Only the player who opened the lobby can start the match")
+        }
+
+        if lobby.teams.iter().any(|team| team.is_empty()) {
+            drop(state);
+            return Self::send_error(player, "Both teams need at least one player")
+        }
+
+        let lobby_id = lobby.id;
+        Self::send_team_members(&state, lobby_id);
+
+        let lobby = state.lobbies.remove(&lobby_id).expect("lobby exists");
+        for member in lobby.members() {
+            if let Some(player) = state.players.get(&member) {
+                player.write().unwrap().in_lobby = None;
+            }
+        }
+
+        let match_id = state.create_team_match(lobby.teams, lobby.options, config.equivalence);
+        Self::start_round(&mut state, match_id)
+    }
+
+    /// Send a message to the teammates of the player, in a lobby or during a team match.
+    pub(super) fn team_chat(player: &AMPlayer, state: &ARWServerState, message: String) -> AppResult<()> {
+        let (self_id, in_game, in_lobby) = {
+            let player = player.read().unwrap();
+            (player.id, player.in_game, player.in_lobby)
+        };
+
+        let state = state.read().unwrap();
+        let teams = match (in_lobby, in_game) {
+            (Some(lobby_id), _) => state.lobbies.get(&lobby_id).map(|lobby| lobby.teams.to_vec()),
+            (_, Some(game_id)) => state.games.get(&game_id)
+                .map(|game| game.read().unwrap().teams.iter().map(|team| team.members.clone()).collect()),
+            _ => None,
+        };
+
+        let Some(team) = teams.into_iter().flatten().find(|team| team.contains(&self_id)) else {
+            drop(state);
+            return Self::send_error(player, "Not in a team")
+        };
+
+        for member in team.into_iter().filter(|id| *id != self_id) {
+            Self::notify(&state, member, Command::TeamMessage(self_id.to_string(), message.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Tell every member of the lobby who is in which team, together with their names.
+    fn send_team_members(state: &ServerState, lobby_id: u32) {
+        let Some(lobby) = state.lobbies.get(&lobby_id) else {
+            return
+        };
+
+        let [first, second] = lobby.teams.clone().map(|team| team.iter().map(|id| id.to_string()).collect::<Vec<_>>());
+        for member in lobby.members() {
+            let notice = Command::TeamMembers(lobby_id.to_string(), first.clone(), second.clone());
+            let result = Self::send_names(state, member, lobby.members()).and_then(|()| Self::send_to(state, member, notice));
+            if let Err(err) = result {
+                eprintln!("Failed to notify player {}: {:?}", member, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lobby() {
+        let mut lobby = TeamLobby::new(1, 10, MatchOptions::default());
+        lobby.join(20, 1);
+        lobby.join(30, 0);
+        lobby.join(10, 1);
+        assert_eq!(lobby.teams, [vec![30], vec![20, 10]]);
+
+        assert!(!lobby.leave(10));
+        assert_eq!(lobby.owner, 30);
+        assert!(!lobby.leave(30));
+        assert!(lobby.leave(20));
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, thread, time::Duration};
+
+use crate::Command;
+
+use super::{get_timestamp, ARWServerState, EndReason, Server, ServerState};
+
+impl Server {
+    /// Seconds left at which both players are reminded of the next deadline.
+    const TIME_WARNINGS: [u64; 4] = [60, 30, 10, 5];
+
+    /// Start the thread which enforces all deadlines, invites and games are checked once per second.
+    /// Solo games get their next hint when it is due, rooms whose hinter did not set a word in time are closed, and tournament matches waiting for busy players are started as soon as both are free.
+    pub fn start_timers(&mut self) {
+        let state = self.state.clone();
+        thread::spawn(move || {
+            // seconds left in each running game at the previous tick
+            let mut last_left = HashMap::new();
+
+            loop {
+                thread::sleep(Duration::from_secs(1));
+
+                let now = get_timestamp();
+                Self::sweep_invites(&state, now);
+                Self::check_games(&state, now, &mut last_left);
+                Self::give_solo_hints(&state, now);
+                Self::close_idle_rooms(&state, now);
+                Self::schedule_tournaments(&mut state.write().unwrap());
+                if state.read().unwrap().retention.is_due(now) {
+                    state.write().unwrap().prune_games(now);
+                }
+            }
+        });
+    }
+
+    /// Give the due hints of solo games and forget the ones which are over.
+    fn give_solo_hints(state: &ARWServerState, now: u64) {
+        let finished = Self::schedule_solo_hints(&state.read().unwrap(), now);
+
+        if !finished.is_empty() {
+            let mut state = state.write().unwrap();
+            for game_id in finished {
+                state.solo_games.remove(&game_id);
+            }
+        }
+    }
+
+    /// End every game with a passed deadline and move its match on to the next round.
+    fn check_games(state: &ARWServerState, now: u64, last_left: &mut HashMap<u32, u64>) {
+        let (timed_out, finished) = Self::check_deadlines(&state.read().unwrap(), now, last_left);
+
+        if !finished.is_empty() {
+            state.write().unwrap().retire_games(finished);
+        }
+
+        // the next round of a match needs the state for itself
+        for game_id in timed_out {
+            if let Err(err) = Self::finish_round(state, game_id) {
+                eprintln!("Failed to finish round of game {}: {:?}", game_id, err);
+            }
+        }
+    }
+
+    /// End every active game with a passed deadline in favour of the player who was not holding it up.
+    /// Returns the ids of the games which timed out and of all games which are no longer active.
+    /// Both players are sent the time left whenever a new turn starts and when a warning is due, so clients can count down.
+    fn check_deadlines(state: &ServerState, now: u64, last_left: &mut HashMap<u32, u64>) -> (Vec<u32>, Vec<u32>) {
+        let mut timed_out = Vec::new();
+        let mut finished = Vec::new();
+
+        for game_id in &state.active_games {
+            let Some(game) = state.games.get(game_id) else {
+                finished.push(*game_id);
+                continue
+            };
+
+            let mut game = game.write().unwrap();
+            if game.is_finished() {
+                last_left.remove(&game.id);
+                finished.push(game.id);
+                continue
+            }
+
+            if let Some(winner) = game.timeout_winner(now) {
+                last_left.remove(&game.id);
+                if let Err(err) = Self::end_game(state, &mut game, Some(winner), EndReason::TimedOut) {
+                    eprintln!("Failed to end game {}: {:?}", game.id, err);
+                }
+                timed_out.push(game.id);
+                finished.push(game.id);
+                continue
+            }
+
+            let Some(left) = game.time_left(now) else {
+                continue
+            };
+
+            let due = match last_left.insert(game.id, left) {
+                Some(previous) => left > previous || Self::TIME_WARNINGS.iter().any(|mark| previous > *mark && left <= *mark),
+                None => true,
+            };
+
+            if !due {
+                continue
+            }
+
+            for player_id in game.notified_players() {
+                if let Err(err) = Self::send_to(state, player_id, Command::TimeLeft(u16::try_from(left).unwrap_or(u16::MAX))) {
+                    eprintln!("Failed to send time left to player {}: {:?}", player_id, err);
+                }
+            }
+        }
+
+        (timed_out, finished)
+    }
+}
@@ -0,0 +1,520 @@
+use std::{collections::BTreeMap, fmt, str::FromStr, sync::Arc};
+
+use serde::Serialize;
+
+use crate::{AppError, AppResult, Command};
+
+use super::{AMPlayer, ARWServerState, Equivalence, MatchOptions, Server, ServerConfig, ServerState};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormat {
+    /// Winners advance to the next round until one is left.
+    SingleElimination,
+    /// Everybody plays everybody once, most won matches wins.
+    RoundRobin,
+}
+
+impl FromStr for TournamentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elimination" | "single-elimination" => Ok(TournamentFormat::SingleElimination),
+            "round-robin" | "roundrobin" => Ok(TournamentFormat::RoundRobin),
+            _ => Err(format!("Unknown tournament format {:?}, available: elimination, round-robin", s)),
+        }
+    }
+}
+
+impl fmt::Display for TournamentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentFormat::SingleElimination => write!(f, "single elimination"),
+            TournamentFormat::RoundRobin => write!(f, "round robin"),
+        }
+    }
+}
+
+/// The lifecycle of a tournament, registration closes once it starts.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentPhase {
+    Registering,
+    Running,
+    Finished,
+}
+
+/// Two players meeting in a tournament round, a single player has a bye and advances right away.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Pairing {
+    pub(super) players: Vec<u32>,
+    /// The match played for the pairing, `None` until both players are free.
+    pub(super) match_id: Option<u32>,
+    pub(super) winner: Option<u32>,
+    pub(super) finished: bool,
+}
+
+impl Pairing {
+    fn new(players: Vec<u32>) -> Self {
+        let bye = players.len() == 1;
+        Self {
+            winner: bye.then(|| players[0]),
+            players,
+            match_id: None,
+            finished: bye,
+        }
+    }
+
+    fn finish(&mut self, winner: Option<u32>) {
+        self.winner = winner;
+        self.finished = true;
+    }
+}
+
+/// A bracket of matches between registered players, the server starts every match once both players are free.
+#[derive(Serialize, Debug)]
+pub struct Tournament {
+    pub(super) id: u32,
+    pub(super) format: TournamentFormat,
+    pub(super) options: MatchOptions,
+    #[serde(skip)]
+    pub(super) equivalence: Equivalence,
+    /// Registered players, in the order of their seeds.
+    pub(super) players: Vec<u32>,
+    /// Display names of the registered players when they registered, players without one are left out.
+    pub(super) names: BTreeMap<u32, String>,
+    /// Usernames of the registered players who logged in with an account, guests are left out.
+    pub(super) usernames: BTreeMap<u32, String>,
+    /// The pairings of every round so far, a single elimination adds each round once the previous one is over.
+    pub(super) rounds: Vec<Vec<Pairing>>,
+    pub(super) phase: TournamentPhase,
+    pub(super) winner: Option<u32>,
+}
+
+impl Tournament {
+    pub(super) fn new(id: u32, format: TournamentFormat, options: MatchOptions, equivalence: Equivalence) -> Self {
+        Self {
+            id,
+            format,
+            options,
+            equivalence,
+            players: Vec::new(),
+            names: BTreeMap::new(),
+            usernames: BTreeMap::new(),
+            rounds: Vec::new(),
+            phase: TournamentPhase::Registering,
+            winner: None,
+        }
+    }
+
+    /// Returns false if registration is closed or the player is already registered.
+    fn register(&mut self, player_id: u32, name: Option<String>, username: Option<String>) -> bool {
+        if self.phase != TournamentPhase::Registering || self.players.contains(&player_id) {
+            return false
+        }
+
+        self.players.push(player_id);
+        if let Some(name) = name {
+            self.names.insert(player_id, name);
+        }
+        if let Some(username) = username {
+            self.usernames.insert(player_id, username);
+        }
+        true
+    }
+
+    /// Returns false if registration is closed or the player was not registered.
+    fn unregister(&mut self, player_id: u32) -> bool {
+        let len = self.players.len();
+        if self.phase == TournamentPhase::Registering {
+            self.players.retain(|id| *id != player_id);
+            self.names.remove(&player_id);
+            self.usernames.remove(&player_id);
+        }
+        self.players.len() != len
+    }
+
+    /// Close the registration and draw the first round, or every round of a round robin.
+    fn start(&mut self) -> Result<(), &'static str> {
+        if self.phase != TournamentPhase::Registering {
+            return Err("Tournament already started")
+        }
+        if self.players.len() < 2 {
+            return Err("A tournament needs at least two players")
+        }
+
+        self.phase = TournamentPhase::Running;
+        self.rounds = match self.format {
+            TournamentFormat::SingleElimination => vec![Self::pair_up(&self.players)],
+            TournamentFormat::RoundRobin => Self::round_robin(&self.players),
+        };
+        self.advance();
+        Ok(())
+    }
+
+    /// Pair the players in order, the last one has a bye if their number is odd.
+    fn pair_up(players: &[u32]) -> Vec<Pairing> {
+        players.chunks(2).map(|players| Pairing::new(players.to_vec())).collect()
+    }
+
+    /// Every round of a round robin by the circle method, the first player stays in place while the others rotate.
+    /// With an odd number of players whoever meets the empty seat sits the round out.
+    fn round_robin(players: &[u32]) -> Vec<Vec<Pairing>> {
+        let mut seats = players.iter().copied().map(Some).collect::<Vec<_>>();
+        if seats.len() % 2 == 1 {
+            seats.push(None);
+        }
+
+        let n = seats.len();
+        (0..n - 1).map(|_| {
+            let round = (0..n / 2)
+                .filter_map(|i| Some(Pairing::new(vec![seats[i]?, seats[n - 1 - i]?])))
+                .collect();
+            seats[1..].rotate_right(1);
+            round
+        }).collect()
+    }
+
+    /// The first round with pairings left to play.
+    fn current_round(&self) -> Option<usize> {
+        self.rounds.iter().position(|round| round.iter().any(|pairing| !pairing.finished))
+    }
+
+    /// Pairings of the current round which still need a match, as `(round, index)`.
+    fn unscheduled(&self) -> Vec<(usize, usize)> {
+        let Some(round) = self.current_round() else {
+            return Vec::new()
+        };
+
+        self.rounds[round].iter().enumerate()
+            .filter(|(_, pairing)| !pairing.finished && pairing.match_id.is_none())
+            .map(|(i, _)| (round, i))
+            .collect()
+    }
+
+    /// Record the result of a finished match, returns false if it was not played for this tournament.
+    /// A single elimination match without a winner is played again.
+    fn record(&mut self, match_id: u32, winner: Option<u32>) -> bool {
+        let format = self.format;
+        let Some(pairing) = self.rounds.iter_mut().flatten().find(|pairing| pairing.match_id == Some(match_id)) else {
+            return false
+        };
+
+        match (format, winner) {
+            (TournamentFormat::SingleElimination, None) => pairing.match_id = None,
+            _ => pairing.finish(winner),
+        }
+
+        self.advance();
+        true
+    }
+
+    /// End a pairing without a match because players left, the one still there wins and nobody does if both left.
+    fn forfeit(&mut self, round: usize, i: usize, present: Option<u32>) {
+        self.rounds[round][i].finish(present);
+        self.advance();
+    }
+
+    /// Draw the next single elimination round once the current one is over, or finish the tournament.
+    fn advance(&mut self) {
+        if self.phase != TournamentPhase::Running || self.current_round().is_some() {
+            return
+        }
+
+        match self.format {
+            TournamentFormat::SingleElimination => {
+                let winners = self.rounds.last().into_iter().flatten().filter_map(|pairing| pairing.winner).collect::<Vec<_>>();
+                if winners.len() > 1 {
+                    self.rounds.push(Self::pair_up(&winners));
+                    return self.advance()
+                }
+                self.winner = winners.first().copied();
+            },
+            TournamentFormat::RoundRobin => self.winner = self.leader(),
+        }
+
+        self.phase = TournamentPhase::Finished;
+    }
+
+    /// The player with the most won matches, `None` if several share the lead.
+    fn leader(&self) -> Option<u32> {
+        let wins = |player_id: u32| self.rounds.iter().flatten().filter(|pairing| pairing.winner == Some(player_id)).count();
+
+        let most = self.players.iter().map(|id| wins(*id)).max()?;
+        match self.players.iter().filter(|id| wins(**id) == most).collect::<Vec<_>>().as_slice() {
+            [leader] => Some(**leader),
+            _ => None,
+        }
+    }
+}
+
+impl Server {
+    /// Open a tournament from the server console, `args` is the format followed by match options like `rules=wordle`.
+    pub(super) fn create_tournament(state: &ARWServerState, config: &Arc<ServerConfig>, args: &str) {
+        let mut args = args.split_whitespace();
+        let format = match args.next().unwrap_or_default().parse() {
+            Ok(format) => format,
+            Err(err) => {
+                println!("{}", err);
+                return
+            },
+        };
+
+        let mut options = MatchOptions::default();
+        if let Err(err) = args.try_for_each(|arg| options.apply(arg)).and_then(|_| options.validate()) {
+            println!("{}", err);
+            return
+        }
+
+        let id = state.write().unwrap().create_tournament(format, config.apply_defaults(options), config.equivalence);
+        println!("Opened tournament {}, players can register now", id);
+    }
+
+    /// Close the registration of a tournament from the server console and start the matches of its first round.
+    pub(super) fn start_tournament(state: &ARWServerState, id: u32) {
+        let mut state = state.write().unwrap();
+        let Some(tournament) = state.tournaments.get_mut(&id) else {
+            println!("Tournament {} not found", id);
+            return
+        };
+
+        if let Err(err) = tournament.start() {
+            println!("Cannot start tournament {}: {}", id, err);
+            return
+        }
+
+        println!("Started tournament {} with {} players", id, tournament.players.len());
+        Self::schedule_tournaments(&mut state);
+    }
+
+    /// Send a short description of every tournament.
+    pub(super) fn list_tournaments(player: &AMPlayer, state: &ARWServerState) -> AppResult<()> {
+        let tournaments = {
+            let state = state.read().unwrap();
+            let mut tournaments = state.tournaments.values().collect::<Vec<_>>();
+            tournaments.sort_by_key(|tournament| tournament.id);
+
+            tournaments.iter().map(|tournament| format!(
+                "{}: {}, {} players, {}",
+                tournament.id, tournament.format, tournament.players.len(), match tournament.phase {
+                    TournamentPhase::Registering => "open for registration",
+                    TournamentPhase::Running => "running",
+                    TournamentPhase::Finished => "finished",
+                },
+            )).collect()
+        };
+
+        Self::send(&mut player.write().unwrap().connection, Command::Tournaments(tournaments))
+    }
+
+    /// Register the player for a tournament which has not started yet.
+    pub(super) fn join_tournament(player: &AMPlayer, state: &ARWServerState, id: String) -> AppResult<()> {
+        let Ok(tournament_id) = id.parse() else {
+            return Self::send_error(player, "Invalid tournament id")
+        };
+
+        let self_id = player.read().unwrap().id;
+        let mut state = state.write().unwrap();
+        let (name, username) = {
+            let player = player.read().unwrap();
+            (player.display_name().map(str::to_string), player.account.clone())
+        };
+        let registered = match state.tournaments.get_mut(&tournament_id) {
+            Some(tournament) => tournament.register(self_id, name, username),
+            None => {
+                drop(state);
+                return Self::send_error(player, "Tournament not found")
+            },
+        };
+
+        if !registered {
+            drop(state);
+            return Self::send_error(player, "Cannot register for this tournament")
+        }
+
+        Self::broadcast_tournament(&state, tournament_id)?;
+        Self::send(&mut player.write().unwrap().connection, Command::Ok)
+    }
+
+    /// Withdraw the registration of the player before the tournament starts.
+    pub(super) fn leave_tournament(player: &AMPlayer, state: &ARWServerState, id: String) -> AppResult<()> {
+        let Ok(tournament_id) = id.parse() else {
+            return Self::send_error(player, "Invalid tournament id")
+        };
+
+        let self_id = player.read().unwrap().id;
+        let mut state = state.write().unwrap();
+        let unregistered = state.tournaments.get_mut(&tournament_id).is_some_and(|tournament| tournament.unregister(self_id));
+
+        if !unregistered {
+            drop(state);
+            return Self::send_error(player, "Not registered for this tournament")
+        }
+
+        Self::broadcast_tournament(&state, tournament_id)?;
+        Self::send(&mut player.write().unwrap().connection, Command::Ok)
+    }
+
+    /// Count a finished match for the tournament it was played for and start whatever can be played next.
+    pub(super) fn finish_tournament_match(state: &mut ServerState, match_id: u32, winner: Option<u32>) -> AppResult<()> {
+        let Some(id) = state.tournaments.values_mut().find_map(|tournament| tournament.record(match_id, winner).then_some(tournament.id)) else {
+            return Ok(())
+        };
+
+        Self::broadcast_tournament(state, id)?;
+        Self::schedule_tournaments(state);
+        Ok(())
+    }
+
+    /// Start a match for every pairing of a current round whose players are both free, called by the timers every second.
+    /// A player who disconnected loses the pairing without playing, if both did nobody wins it.
+    pub(super) fn schedule_tournaments(state: &mut ServerState) {
+        if state.tournaments.is_empty() {
+            return
+        }
+
+        let mut changed = Vec::new();
+        let ids = state.tournaments.keys().copied().collect::<Vec<_>>();
+
+        for id in ids {
+            for (round, i) in state.tournaments[&id].unscheduled() {
+                let tournament = &state.tournaments[&id];
+                let players = tournament.rounds[round][i].players.clone();
+                let present = players.iter().copied()
+                    .filter(|id| state.players.get(id).is_some_and(|player| player.read().unwrap().connected))
+                    .collect::<Vec<_>>();
+
+                if present.len() < players.len() {
+                    state.tournaments.get_mut(&id).expect("tournament exists").forfeit(round, i, present.first().copied());
+                    changed.push(id);
+                    continue
+                }
+
+                let available = players.iter().all(|id| !state.players[id].read().unwrap().is_busy());
+                if !available {
+                    continue
+                }
+
+                let (options, equivalence) = (tournament.options.clone(), tournament.equivalence);
+                let match_id = state.create_match(players[0], players[1], options, equivalence, None);
+                state.tournaments.get_mut(&id).expect("tournament exists").rounds[round][i].match_id = Some(match_id);
+                changed.push(id);
+
+                if let Err(err) = Self::start_round(state, match_id) {
+                    eprintln!("Failed to start tournament match {}: {:?}", match_id, err);
+                }
+            }
+        }
+
+        changed.dedup();
+        for id in changed {
+            if let Err(err) = Self::broadcast_tournament(state, id) {
+                eprintln!("Failed to broadcast tournament {}: {:?}", id, err);
+            }
+        }
+    }
+
+    fn broadcast_tournament(state: &ServerState, id: u32) -> AppResult<()> {
+        let Some(tournament) = state.tournaments.get(&id) else {
+            return Ok(())
+        };
+        let json = serde_json::to_string(tournament).map_err(AppError::Serde)?;
+
+        let mut subs = state.subscribers.lock().unwrap();
+        for (_, conns) in subs.iter_mut() {
+            if let Some(conn) = conns {
+                if Self::send(conn, Command::TournamentState(json.clone())).is_err() {
+                    *conns = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(format: TournamentFormat, players: &[u32]) -> Tournament {
+        let mut tournament = Tournament::new(1, format, MatchOptions::default(), Equivalence::default());
+        for id in players {
+            assert!(tournament.register(*id, None, None));
+        }
+        tournament.start().unwrap();
+        tournament
+    }
+
+    /// Play every pairing of the current round, the first player of each pairing wins.
+    fn play_round(tournament: &mut Tournament, next_match: &mut u32) {
+        for (round, i) in tournament.unscheduled() {
+            let pairing = &mut tournament.rounds[round][i];
+            pairing.match_id = Some(*next_match);
+            let winner = pairing.players[0];
+
+            assert!(tournament.record(*next_match, Some(winner)));
+            *next_match += 1;
+        }
+    }
+
+    #[test]
+    fn test_single_elimination() {
+        let mut tournament = tournament(TournamentFormat::SingleElimination, &[1, 2, 3, 4, 5]);
+        assert_eq!(tournament.rounds[0].len(), 3);
+        // the fifth player has a bye
+        assert_eq!(tournament.rounds[0][2].winner, Some(5));
+
+        let mut next_match = 1;
+        play_round(&mut tournament, &mut next_match);
+        assert_eq!(tournament.rounds[1].iter().map(|pairing| pairing.players.clone()).collect::<Vec<_>>(), vec![vec![1, 3], vec![5]]);
+
+        // a draw is played again
+        let unscheduled = tournament.unscheduled();
+        let (round, i) = unscheduled[0];
+        tournament.rounds[round][i].match_id = Some(99);
+        assert!(tournament.record(99, None));
+        assert_eq!(tournament.unscheduled(), unscheduled);
+
+        play_round(&mut tournament, &mut next_match);
+        assert_eq!(tournament.rounds[2][0].players, vec![1, 5]);
+        play_round(&mut tournament, &mut next_match);
+        assert_eq!(tournament.phase, TournamentPhase::Finished);
+        assert_eq!(tournament.winner, Some(1));
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let mut tournament = tournament(TournamentFormat::SingleElimination, &[1, 2, 3, 4]);
+        // both players of the first pairing left
+        tournament.forfeit(0, 0, None);
+        assert_eq!(tournament.rounds[0][0].winner, None);
+
+        let mut next_match = 1;
+        play_round(&mut tournament, &mut next_match);
+        assert_eq!(tournament.phase, TournamentPhase::Finished);
+        assert_eq!(tournament.winner, Some(3));
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut tournament = tournament(TournamentFormat::RoundRobin, &[1, 2, 3]);
+        assert_eq!(tournament.rounds.len(), 3);
+        assert!(tournament.rounds.iter().all(|round| round.len() == 1));
+
+        let mut next_match = 1;
+        while tournament.phase == TournamentPhase::Running {
+            play_round(&mut tournament, &mut next_match);
+        }
+
+        let mut pairs = tournament.rounds.iter().flatten().map(|pairing| {
+            let mut players = pairing.players.clone();
+            players.sort();
+            players
+        }).collect::<Vec<_>>();
+        pairs.sort();
+        assert_eq!(pairs, vec![vec![1, 2], vec![1, 3], vec![2, 3]]);
+        assert_eq!(tournament.winner, Some(1));
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{AppResult, ReadBytes, WriteBytes};
+
+/// Normalize text to NFC, so the same visible word always has the same code points.
+pub fn normalize(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Split text into user-perceived characters, "ë" is one grapheme even when written as "e" + "◌̈".
+pub fn graphemes(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+}
+
+/// Which differences between a guess and the secret word are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Equivalence {
+    pub ignore_case: bool,
+    /// Treat letters with diacritics like their base letter, "ë" matches "e".
+    pub ignore_diacritics: bool,
+    pub ignore_punctuation: bool,
+}
+
+impl Default for Equivalence {
+    fn default() -> Self {
+        Self {
+            ignore_case: true,
+            ignore_diacritics: false,
+            ignore_punctuation: false,
+        }
+    }
+}
+
+impl Equivalence {
+    /// Reduce text to the form used for comparisons.
+    pub fn fold(&self, text: &str) -> String {
+        let mut folded: String = if self.ignore_diacritics {
+            text.nfd().filter(|c| !is_combining_mark(*c)).collect()
+        } else {
+            text.nfd().collect()
+        };
+
+        if self.ignore_case {
+            folded = folded.to_lowercase();
+        }
+
+        if self.ignore_punctuation {
+            folded.retain(|c| !c.is_whitespace() && !c.is_ascii_punctuation() && !is_unicode_punctuation(c));
+        }
+
+        folded.nfc().collect()
+    }
+
+    pub fn matches(&self, a: &str, b: &str) -> bool {
+        self.fold(a) == self.fold(b)
+    }
+}
+
+/// Wordle style verdict for a single grapheme of a guess.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LetterFeedback {
+    /// In the word at this position.
+    Correct,
+    /// In the word, but at another position.
+    Present,
+    Absent,
+}
+
+impl WriteBytes for LetterFeedback {
+    fn write(&self, buffer: &mut dyn Write) -> AppResult<usize> {
+        (*self as u8).write(buffer)
+    }
+}
+
+impl ReadBytes for LetterFeedback {
+    fn read(buffer: &mut std::slice::Iter<u8>) -> Option<Self> where Self: Sized {
+        match u8::read(buffer)? {
+            0 => Some(LetterFeedback::Correct),
+            1 => Some(LetterFeedback::Present),
+            2 => Some(LetterFeedback::Absent),
+            _ => None,
+        }
+    }
+}
+
+/// Compare a guess with the word position by position, returns `None` if they have a different number of graphemes.
+/// A letter guessed more often than it occurs in the word is only marked present as many times as it occurs.
+pub fn letter_feedback(word: &str, guess: &str, equivalence: &Equivalence) -> Option<Vec<LetterFeedback>> {
+    let word = graphemes(word).into_iter().map(|g| equivalence.fold(g)).collect::<Vec<_>>();
+    let guess = graphemes(guess).into_iter().map(|g| equivalence.fold(g)).collect::<Vec<_>>();
+
+    if word.len() != guess.len() {
+        return None
+    }
+
+    let mut feedback = vec![LetterFeedback::Absent; guess.len()];
+    let mut unmatched = Vec::new();
+
+    for (i, (w, g)) in word.iter().zip(&guess).enumerate() {
+        if w == g {
+            feedback[i] = LetterFeedback::Correct;
+        } else {
+            unmatched.push(w);
+        }
+    }
+
+    for (i, g) in guess.iter().enumerate() {
+        if feedback[i] == LetterFeedback::Correct {
+            continue
+        }
+
+        if let Some(pos) = unmatched.iter().position(|w| *w == g) {
+            unmatched.swap_remove(pos);
+            feedback[i] = LetterFeedback::Present;
+        }
+    }
+
+    Some(feedback)
+}
+
+/// Dashes, quotes and similar marks outside of ASCII, like "–" or "„".
+fn is_unicode_punctuation(c: char) -> bool {
+    matches!(c, '\u{2010}'..='\u{2027}' | '\u{2030}'..='\u{205E}' | '\u{00A1}' | '\u{00AB}' | '\u{00BB}' | '\u{00BF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graphemes() {
+        let decomposed = "Bre\u{0301}ck";
+        assert_eq!(graphemes(decomposed).len(), 5);
+        assert_eq!(graphemes(&normalize(decomposed)), ["B", "r", "é", "c", "k"]);
+        assert_eq!(normalize(decomposed), "Bréck");
+    }
+
+    #[test]
+    fn test_equivalence() {
+        let exact = Equivalence {
+            ignore_case: false,
+            ignore_diacritics: false,
+            ignore_punctuation: false,
+        };
+        assert!(exact.matches("Lëtzebuerg", "Le\u{0308}tzebuerg"));
+        assert!(!exact.matches("Lëtzebuerg", "lëtzebuerg"));
+
+        let loose = Equivalence {
+            ignore_case: true,
+            ignore_diacritics: true,
+            ignore_punctuation: true,
+        };
+        assert!(loose.matches("Lëtzebuerg", "letzebuerg"));
+        assert!(loose.matches("Gromperekichelcher", "grompere-kichelcher!"));
+        assert!(loose.matches("Moien alleguer", "moienalleguer"));
+        assert!(!loose.matches("Bréck", "Brack"));
+    }
+
+    #[test]
+    fn test_letter_feedback() {
+        use LetterFeedback::*;
+        let equivalence = Equivalence::default();
+
+        assert_eq!(letter_feedback("Kéis", "kees", &equivalence), Some(vec![Correct, Absent, Absent, Correct]));
+        assert_eq!(letter_feedback("abbey", "babes", &equivalence), Some(vec![Present, Present, Correct, Correct, Absent]));
+        assert_eq!(letter_feedback("abbey", "abb", &equivalence), None);
+    }
+}
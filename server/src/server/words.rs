@@ -0,0 +1,157 @@
+use std::fs;
+
+use rand::seq::IteratorRandom;
+
+use crate::AppResult;
+
+use super::normalize;
+
+/// Words the server can pick as the secret word, one per line in a plain text file.
+pub struct WordList {
+    words: Vec<String>,
+}
+
+impl WordList {
+    /// The list shipped with the server for one of the [`MatchOptions::LANGUAGES`](super::MatchOptions::LANGUAGES).
+    pub fn builtin(language: &str) -> Option<Self> {
+        let text = match language {
+            "lb" => include_str!("../../words/lb.txt"),
+            "de" => include_str!("../../words/de.txt"),
+            "fr" => include_str!("../../words/fr.txt"),
+            "en" => include_str!("../../words/en.txt"),
+            _ => return None,
+        };
+
+        Some(Self::parse(text))
+    }
+
+    /// Read a word list, empty lines and lines starting with `#` are skipped.
+    pub fn load(path: &str) -> AppResult<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    fn parse(text: &str) -> Self {
+        Self {
+            words: parse_words(text),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(String::as_str)
+    }
+
+    /// A random word out of those accepted, see [`ServerConfig::accepts`](super::ServerConfig::accepts).
+    pub fn pick(&self, accepts: impl Fn(&str) -> bool) -> Option<String> {
+        self.words.iter()
+            .filter(|word| accepts(word))
+            .choose(&mut rand::rng())
+            .cloned()
+    }
+}
+
+/// One word per line in NFC form, empty lines and lines starting with `#` are skipped.
+pub(super) fn parse_words(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(normalize)
+        .collect()
+}
+
+/// Words with the hints a bot gives for them, one word per line followed by a colon and the hints separated by semicolons.
+pub struct HintList {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl HintList {
+    /// The hints shipped with the server, only for some languages.
+    pub fn builtin(language: &str) -> Option<Self> {
+        let text = match language {
+            "lb" => include_str!("../../words/lb-hints.txt"),
+            "en" => include_str!("../../words/en-hints.txt"),
+            _ => return None,
+        };
+
+        Some(Self::parse(text))
+    }
+
+    /// Read a hint file, empty lines, lines starting with `#` and lines without hints are skipped.
+    pub fn load(path: &str) -> AppResult<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    fn parse(text: &str) -> Self {
+        let entries = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(word, hints)| {
+                let hints = hints.split(';').map(str::trim).filter(|hint| !hint.is_empty()).map(normalize).collect::<Vec<_>>();
+                (normalize(word.trim()), hints)
+            })
+            .filter(|(word, hints)| !word.is_empty() && !hints.is_empty())
+            .collect();
+
+        Self {
+            entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// A random word with its hints, like [`WordList::pick`].
+    pub fn pick(&self, accepts: impl Fn(&str) -> bool) -> Option<(String, Vec<String>)> {
+        self.entries.iter()
+            .filter(|(word, _)| accepts(word))
+            .choose(&mut rand::rng())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graphemes, MatchOptions};
+
+    #[test]
+    fn test_pick() {
+        let list = WordList::parse("# comment\n\nHaus\n  Fënster \nKaz\n");
+        assert_eq!(list.len(), 3);
+
+        let mut options = MatchOptions {
+            min_word_length: Some(4),
+            max_word_length: Some(4),
+            ..MatchOptions::default()
+        };
+        assert_eq!(list.pick(|word| options.allows_length(graphemes(word).len())).as_deref(), Some("Haus"));
+
+        options.min_word_length = Some(10);
+        options.max_word_length = None;
+        assert_eq!(list.pick(|word| options.allows_length(graphemes(word).len())), None);
+
+        for language in MatchOptions::LANGUAGES {
+            assert!(WordList::builtin(language).is_some_and(|list| !list.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_hint_list() {
+        let list = HintList::parse("# comment\nKaz: a pet; it purrs ;\nHond:\nno hints\n");
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pick(|_| true), Some(("Kaz".to_string(), vec!["a pet".to_string(), "it purrs".to_string()])));
+    }
+}
@@ -3,9 +3,11 @@ function updateGames() {
     if (res.ok) {
       res.json().then(res => {
         const games = res.games
+        const matches = res.matches
+        const tournaments = JSON.stringify(res.tournaments)
 
         if (window.games) {
-          if (window.games.length === games.length) {
+          if (window.games.length === games.length && window.matches.length === matches.length && window.tournaments === tournaments) {
             for (let i = 0; i < games.length; i++) {
               if (window.games[i].id !== games[i].id) {
                 break;
@@ -16,14 +18,27 @@ function updateGames() {
         }
 
         window.games = games
+        window.matches = matches
+        window.tournaments = tournaments
         const gamesEl = document.getElementById("games")
         gamesEl.innerHTML = ''
         gamesEl.innerHTML = games.map(gameHtml).join('')
+
+        const matchesEl = document.getElementById("matches")
+        matchesEl.innerHTML = matches.filter(match => match.options.rounds > 1).map(matchHtml).join('')
+
+        const tournamentsEl = document.getElementById("tournaments")
+        tournamentsEl.innerHTML = res.tournaments.map(tournamentHtml).join('')
       })
     }
   })
 }
 
+// the name with the id, or "Player" and the id for players without a name
+function playerName(names, id) {
+  return names[id] ? `${names[id]} (${id})` : `Player ${id}`
+}
+
 function gameHtml(game) {
   const winner = (id) => {
     if (id === game.winner) {
@@ -33,22 +48,111 @@ function gameHtml(game) {
     }
   }
 
+  // the name with the id, or only the id for players without a name
+  const player = (id) => game.names[id] ? `${game.names[id]} (${id})` : `${id}`
+
   const lines = (arr) => {
     return arr.map(line => line).join("<br>")
   }
 
+  const feedbackColors = {
+    correct: "bg-lime-500",
+    present: "bg-yellow-500",
+    absent: "bg-gray-500",
+  }
+
+  const options = () => {
+    const { max_wrong_guesses, time_limit, turn_limit, hint_limit, min_word_length, max_word_length } = game.options
+    return [
+      max_wrong_guesses !== null ? `${game.wrong_guesses}/${max_wrong_guesses} wrong guesses` : null,
+      time_limit !== null ? `${time_limit}s time limit` : null,
+      turn_limit !== null ? `${turn_limit}s per turn` : null,
+      hint_limit !== null ? `${hint_limit} hints` : null,
+      min_word_length !== null || max_word_length !== null ? `length ${min_word_length ?? 1}-${max_word_length ?? ""}` : null,
+    ].filter(option => option !== null).join("<br>")
+  }
+
+  const guesses = () => {
+    if (game.options.ruleset !== "wordle") {
+      return lines(game.guesses)
+    }
+
+    const segmenter = new Intl.Segmenter()
+    return game.guesses.map((guess, i) => {
+      const feedback = game.feedback[i]
+      if (!feedback) return guess
+
+      return [...segmenter.segment(guess)]
+        .map(({ segment }, j) => `<span class="${feedbackColors[feedback[j]]} px-1">${segment}</span>`)
+        .join("")
+    }).join("<br>")
+  }
+
   return `
 <div class="flex flex-col gap-2 border-4 rounded-xl p-2 min-w-[400px]">
   <p class="text-center text-xl">
-    <span class="${winner(game.hinter)}"> Hinter ${game.hinter}</span>
+    <span class="${winner(game.hinter)}"> ${game.hinter === 0 ? "Server" : `Hinter ${player(game.hinter)}`}</span>
     vs 
-    <span class="${winner(game.guesser)}">Guesser ${game.guesser}</span>
+    <span class="${winner(game.guesser)}">Guesser ${player(game.guesser)}</span>
   </p> 
   <div class="[&>p]:text-gray-500 text-start gap-x-4 grid grid-cols-[60px_auto]">
     <p>Finished</p><div>${new Date(game.timestamp).toLocaleTimeString()}</div>
+    ${game.options.rounds > 1 ? `<p>Match</p><div>${game.match_id}, round ${game.round} of ${game.options.rounds}</div>` : ""}
+    ${game.room_id !== null ? `<p>Room</p><div>${game.room_id}</div>` : ""}
+    ${game.teams.length ? `<p>Teams</p><div>${game.teams.map(team => `${team.members.map(player).join(", ")}: ${team.score} points`).join("<br>")}</div>` : ""}
+    ${game.previous_game !== null ? `<p>After</p><div>Game ${game.previous_game}</div>` : ""}
+    <p>Rules</p><div>${game.options.ruleset} (${game.options.language})</div>
+    <p>Options</p><div>${options()}</div>
+    <p>Score</p><div>${game.score}</div>
+    <p>Reason</p><div>${(game.end_reason ?? "").replaceAll("_", " ")}</div>
     <p>Word</p><div>${game.word}</div>
     <p>Hints</p><div>${lines(game.hints)}</div>
-    <p>Guesses</p><div>${lines(game.guesses)}</div>
+    <p>Guesses</p><div>${guesses()}</div>
+    ${game.options.ruleset === "reveal" ? `<p>Reveals</p><div class="font-mono">${lines(game.reveals)}</div>` : ""}
+  </div>
+</div>
+`
+}
+
+function matchHtml(match) {
+  const [first, second] = match.players
+  const winner = (id) => id === match.result?.winner ? "text-lime-500" : "text-red-500"
+
+  return `
+<div class="flex flex-col gap-2 border-4 rounded-xl p-2 min-w-[400px]">
+  <p class="text-center text-xl">
+    Match ${match.id}:
+    <span class="${winner(first)}">${playerName(match.names, first)}</span>
+    ${match.wins[0]} - ${match.wins[1]}
+    <span class="${winner(second)}">${playerName(match.names, second)}</span>
+  </p>
+  <div class="[&>p]:text-gray-500 text-start gap-x-4 grid grid-cols-[60px_auto]">
+    <p>Rules</p><div>${match.options.ruleset}, best of ${match.options.rounds}</div>
+    <p>Score</p><div>${match.score[0]} - ${match.score[1]}</div>
+    <p>Games</p><div>${match.games.join(", ")}</div>
+  </div>
+</div>
+`
+}
+
+function tournamentHtml(tournament) {
+  const pairing = (pairing) => pairing.players
+    .map(id => `<span class="${pairing.winner === id ? "text-lime-500" : ""}">${playerName(tournament.names, id)}</span>`)
+    .join(" vs ") + (pairing.match_id !== null ? ` (match ${pairing.match_id})` : "")
+
+  const rounds = tournament.rounds
+    .map((round, i) => `<p>Round ${i + 1}</p><div>${round.map(pairing).join("<br>")}</div>`)
+    .join("")
+
+  return `
+<div class="flex flex-col gap-2 border-4 rounded-xl p-2 min-w-[400px]">
+  <p class="text-center text-xl">
+    Tournament ${tournament.id}: ${tournament.format.replaceAll("_", " ")}
+  </p>
+  <div class="[&>p]:text-gray-500 text-start gap-x-4 grid grid-cols-[60px_auto]">
+    <p>Status</p><div>${tournament.phase}${tournament.winner !== null ? `, won by ${playerName(tournament.names, tournament.winner)}` : ""}</div>
+    <p>Players</p><div>${tournament.players.map(id => playerName(tournament.names, id)).join(", ")}</div>
+    ${rounds}
   </div>
 </div>
 `
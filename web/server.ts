@@ -88,19 +88,91 @@ class Connection {
   }
 }
 
+type MatchOptions = {
+  ruleset: string
+  language: "lb" | "de" | "fr" | "en"
+  max_wrong_guesses: number | null
+  time_limit: number | null
+  turn_limit: number | null
+  hint_limit: number | null
+  min_word_length: number | null
+  max_word_length: number | null
+  rounds: number
+}
+
 type Game = {
   timestamp: number
   id: number
   hinter: number
   guesser: number
+  match_id: number | null
+  round: number
+  room_id: number | null
+  teams: Team[]
+  names: Record<number, string>
+  usernames: Record<number, string>
+  previous_game: number | null
+  options: MatchOptions
   word: string
   guesses: string[]
   hints: string[]
+  reveals: string[]
+  feedback: (string[] | null)[]
+  wrong_guesses: number
+  winner: number | null
+  score: number
+  end_reason: string | null
+}
+
+type Team = {
+  members: number[]
+  score: number
+}
+
+type MatchResult = {
+  winner: number | null
+  wins: [number, number]
+  score: [number, number]
+  timestamp: number
+}
+
+type Match = {
+  id: number
+  players: [number, number]
+  options: MatchOptions
+  games: number[]
+  previous_game: number | null
+  teams: [number[], number[]] | null
+  names: Record<number, string>
+  usernames: Record<number, string>
+  wins: [number, number]
+  score: [number, number]
+  result: MatchResult | null
+}
+
+type Pairing = {
+  players: number[]
+  match_id: number | null
+  winner: number | null
+  finished: boolean
+}
+
+type Tournament = {
+  id: number
+  format: "single_elimination" | "round_robin"
+  options: MatchOptions
+  players: number[]
+  names: Record<number, string>
+  usernames: Record<number, string>
+  rounds: Pairing[][]
+  phase: "registering" | "running" | "finished"
   winner: number | null
 }
 
 const connection = new Connection()
 const games: Game[] = []
+const matches: Match[] = []
+const tournaments: Tournament[] = []
 
 async function subscribeToGameUpdates() {
   const passBytes = new TextEncoder().encode(PASSWORD)
@@ -122,6 +194,8 @@ async function subscribeToGameUpdates() {
 
 async function updateGames() {
   games.splice(0, games.length)
+  matches.splice(0, matches.length)
+  tournaments.splice(0, tournaments.length)
   while (true) {
     console.log("Waiting for data to update games...")
 
@@ -130,6 +204,27 @@ async function updateGames() {
     const dataBytes = await connection.readBytes(len)
     const stringJson = new TextDecoder().decode(dataBytes.buffer.slice(3)) // skip the byte type and length
 
+    // finished matches arrive as MatchResult (253), games as SubscribeToGames (254)
+    if (dataBytes[0] === 253) {
+      const match = JSON.parse(stringJson) as Match
+      console.log("Received match with id:", match.id)
+      matches.push(match)
+      continue
+    }
+
+    // tournaments (252) are sent again whenever their bracket changes
+    if (dataBytes[0] === 252) {
+      const tournament = JSON.parse(stringJson) as Tournament
+      console.log("Received tournament with id:", tournament.id)
+      const i = tournaments.findIndex(t => t.id === tournament.id)
+      if (i === -1) {
+        tournaments.push(tournament)
+      } else {
+        tournaments[i] = tournament
+      }
+      continue
+    }
+
     const json = JSON.parse(stringJson) as Game;
     console.log("Received game with id:", json.id)
 
@@ -138,10 +233,23 @@ async function updateGames() {
       id: json.id,
       hinter: json.hinter,
       guesser: json.guesser,
+      match_id: json.match_id,
+      round: json.round,
+      room_id: json.room_id,
+      teams: json.teams,
+      names: json.names ?? {},
+      usernames: json.usernames ?? {},
+      previous_game: json.previous_game,
+      options: json.options,
       word: json.word,
       guesses: json.guesses,
       hints: json.hints,
+      reveals: json.reveals,
+      feedback: json.feedback,
+      wrong_guesses: json.wrong_guesses,
       winner: json.winner,
+      score: json.score,
+      end_reason: json.end_reason,
     })
   }
 }
@@ -178,7 +286,7 @@ Deno.serve({ port: PORT }, (req) => {
 
   // API
   if (url.pathname === "/games" && req.method === "GET") {
-    return new Response(JSON.stringify({ games }), {
+    return new Response(JSON.stringify({ games, matches, tournaments }), {
       headers: { "Content-Type": "application/json" },
     });
   }